@@ -0,0 +1,349 @@
+//! コマンドライン引数のパーサ
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::error;
+use std::fmt;
+
+/// オプションの定義
+pub struct Opt {
+    /// オプション名の一覧。先頭の名前を解析結果のキーとする
+    pub names: &'static [&'static str],
+    /// 値の名前。値を取らないフラグの場合はNone
+    pub value: Option<&'static str>,
+}
+
+/// コマンドライン引数の解析に失敗した場合のエラー
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// 定義されていないオプション
+    Unknown(String),
+    /// 値を取るオプションに値が指定されていない
+    MissingValue(String),
+    /// フラグに「--opt=value」の形式で値が指定された
+    UnexpectedValue(String),
+    /// 同時に指定できないオプションが指定された
+    Conflict(String, String),
+    /// 位置引数が多すぎる
+    TooManyArguments(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Unknown(name)          => write!(f, "不明なオプション: {}", name),
+            Error::MissingValue(name)     => write!(f, "{} には値を指定してください", name),
+            Error::UnexpectedValue(name)  => write!(f, "{} には値を指定できません", name),
+            Error::Conflict(a, b)         => write!(f, "{} と {} は同時に指定できません", a, b),
+            Error::TooManyArguments(arg)  => write!(f, "不明な引数: {}", arg),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// オプションの定義に従ってコマンドライン引数を解析するパーサ
+///
+/// 解析結果はオプションの先頭の名前をキー、値をバリューとするHashMapで返す。
+/// フラグの場合はバリューにもオプション名を格納する。
+///
+/// ```
+/// use mtools::args::{Opt, Parser};
+///
+/// const OPTS: &[Opt] = &[
+///     Opt { names: &["-F", "--fields"], value: Some("FIELDS") },
+///     Opt { names: &["--no-header"], value: None },
+/// ];
+/// let parser = Parser::new(OPTS).positional(&["file"]);
+/// let options = parser.parse(vec!["--fields=id,title", "--no-header", "data.csv"]).unwrap();
+/// assert_eq!("id,title", options["-F"]);
+/// assert_eq!("data.csv", options["file"]);
+/// ```
+pub struct Parser {
+    opts: Vec<&'static [Opt]>,
+    positional: &'static [&'static str],
+    exclusive: &'static [&'static [&'static str]],
+}
+
+impl Parser {
+    /// # Arguments
+    /// * `opts` - オプションの定義
+    pub fn new(opts: &'static [Opt]) -> Self {
+        Parser { opts: vec![opts], positional: &[], exclusive: &[] }
+    }
+
+    /// オプションの定義を追加する。複数のコマンドで共通のオプションを追加する場合に利用する
+    ///
+    /// # Arguments
+    /// * `opts` - 追加するオプションの定義
+    pub fn with_opts(mut self, opts: &'static [Opt]) -> Self {
+        self.opts.push(opts);
+        self
+    }
+
+    /// 位置引数の名前を指定する。位置引数は先頭から順にこの名前をキーとして格納する
+    ///
+    /// 最後の名前を「files...」のように「...」で終わる名前にすると、残りの位置引数を全て「files」をキーとして格納する。
+    /// (値は values で取得する)
+    ///
+    /// # Arguments
+    /// * `names` - 位置引数の名前
+    pub fn positional(self, names: &'static [&'static str]) -> Self {
+        Parser { positional: names, ..self }
+    }
+
+    /// 同時に指定できないオプションのグループを指定する
+    ///
+    /// # Arguments
+    /// * `groups` - オプションの先頭の名前のグループ
+    pub fn exclusive(self, groups: &'static [&'static [&'static str]]) -> Self {
+        Parser { exclusive: groups, ..self }
+    }
+
+    /// 全てのオプションの先頭の名前を返す
+    pub fn names(&self) -> Vec<&'static str> {
+        self.opts.iter().flat_map(|opts| opts.iter()).map(|opt| opt.names[0]).collect()
+    }
+
+    /// key と同時に指定できないオプションが options に含まれているかどうかを返す
+    ///
+    /// # Arguments
+    /// * `key`     - オプションの先頭の名前
+    /// * `options` - 解析済みのオプション
+    pub fn conflicts(&self, key: &str, options: &HashMap<String, String>) -> bool {
+        self.exclusive.iter()
+            .filter(|group| group.contains(&key))
+            .any(|group| group.iter().any(|name| *name != key && options.contains_key(*name)))
+    }
+
+    fn find(&self, name: &str) -> Option<&'static Opt> {
+        self.opts.iter().flat_map(|opts| opts.iter()).find(|opt| opt.names.contains(&name))
+    }
+
+    /// コマンドライン引数を解析する
+    ///
+    /// 「--opt value」「--opt=value」「-dvalue」の形式で値を指定できる。「--」以降の引数は全て位置引数とみなす。
+    ///
+    /// # Arguments
+    /// * `args` - プログラム名を除いたコマンドライン引数
+    pub fn parse<I, S>(&self, args: I) -> Result<HashMap<String, String>, Error>
+        where I: IntoIterator<Item = S>, S: Into<String> {
+        self.parse_until(args, &[])
+    }
+
+    /// コマンドライン引数を解析する。stop のフラグが指定された時点で、残りの引数を解析せずに結果を返す
+    ///
+    /// --help のように他のオプションの検証より優先するフラグに利用する。
+    /// オプションの値や「--」以降の引数はフラグとみなさない。
+    ///
+    /// # Arguments
+    /// * `args` - プログラム名を除いたコマンドライン引数
+    /// * `stop` - 解析を終了するフラグの先頭の名前
+    pub fn parse_until<I, S>(&self, args: I, stop: &[&str]) -> Result<HashMap<String, String>, Error>
+        where I: IntoIterator<Item = S>, S: Into<String> {
+        let mut options = HashMap::new();
+        let mut positional = 0;
+        let mut args = args.into_iter().map(|arg| arg.into());
+        let mut terminated = false;
+        while let Some(arg) = args.next() {
+            if terminated || arg == "-" || !arg.starts_with('-') {
+                match self.positional.get(positional) {
+                    Some(key) if key.ends_with("...") => {
+                        // 可変長の位置引数は\0区切りで格納する
+                        let values = options.entry(key.trim_end_matches("...").to_string()).or_insert_with(String::new);
+                        if !values.is_empty() {
+                            values.push('\0');
+                        }
+                        values.push_str(&arg);
+                    }
+                    Some(key) => {
+                        options.insert(key.to_string(), arg);
+                        positional += 1;
+                    }
+                    None => return Err(Error::TooManyArguments(arg)),
+                }
+                continue;
+            }
+            if arg == "--" {
+                terminated = true;
+                continue;
+            }
+            // 「--opt=value」「-dvalue」の形式の場合はオプション名と値に分割する
+            let (name, inline) = if arg.starts_with("--") {
+                match arg.find('=') {
+                    Some(i) => (arg[..i].to_string(), Some(arg[i + 1..].to_string())),
+                    None    => (arg.clone(), None),
+                }
+            } else {
+                let i = arg.char_indices().nth(2).map(|(i, _)| i).unwrap_or_else(|| arg.len());
+                if i < arg.len() && self.find(&arg).is_none() {
+                    (arg[..i].to_string(), Some(arg[i..].to_string()))
+                } else {
+                    (arg.clone(), None)
+                }
+            };
+            let opt = self.find(&name).ok_or_else(|| Error::Unknown(arg.clone()))?;
+            let key = opt.names[0].to_string();
+            match (opt.value, inline) {
+                (Some(_), Some(value)) => {
+                    options.insert(key, value);
+                }
+                (Some(_), None) => {
+                    let value = args.next().ok_or_else(|| Error::MissingValue(name))?;
+                    options.insert(key, value);
+                }
+                (None, Some(_)) => return Err(Error::UnexpectedValue(name)),
+                (None, None) => {
+                    let stopped = stop.contains(&key.as_str());
+                    options.insert(key.clone(), key);
+                    if stopped {
+                        return Ok(options);
+                    }
+                }
+            }
+        }
+        for group in self.exclusive {
+            let specified: Vec<&&str> = group.iter().filter(|name| options.contains_key(**name)).collect();
+            if specified.len() > 1 {
+                return Err(Error::Conflict(specified[0].to_string(), specified[1].to_string()));
+            }
+        }
+        Ok(options)
+    }
+
+    /// オプションの見出し行を生成したヘルプを返す
+    ///
+    /// text の「    -f:」のようにオプションの先頭の名前だけを記述した見出し行を、
+    /// 「    -f, --field-numbers <FIELDS>:」のように全ての名前と値の名前を含んだ見出し行に置き換える。
+    ///
+    /// # Arguments
+    /// * `text` - ヘルプの本文
+    pub fn help(&self, text: &str) -> String {
+        let mut ret = String::with_capacity(text.len());
+        for line in text.lines() {
+            match Self::heading(line).and_then(|name| self.find(name)) {
+                Some(opt) => {
+                    ret.push_str("    ");
+                    ret.push_str(&opt.names.join(", "));
+                    if let Some(value) = opt.value {
+                        ret.push_str(&format!(" <{}>", value));
+                    }
+                    ret.push(':');
+                }
+                None => ret.push_str(line),
+            }
+            ret.push('\n');
+        }
+        ret
+    }
+
+    /// ヘルプの本文の見出し行に記述されたオプション名の一覧を返す
+    ///
+    /// # Arguments
+    /// * `text` - ヘルプの本文
+    pub fn headings(text: &str) -> Vec<&str> {
+        text.lines().filter_map(Self::heading).collect()
+    }
+
+    fn heading(line: &str) -> Option<&str> {
+        if line.starts_with("    -") && line.ends_with(':') && !line.starts_with("     ") {
+            Some(&line[4..line.len() - 1])
+        } else {
+            None
+        }
+    }
+}
+
+/// 可変長の位置引数の値の一覧を返す
+///
+/// # Arguments
+/// * `options` - 解析済みのオプション
+/// * `key`     - 位置引数の名前(末尾の「...」を除いた名前)
+pub fn values<'a, S: BuildHasher>(options: &'a HashMap<String, String, S>, key: &str) -> Vec<&'a str> {
+    options.get(key).map_or_else(Vec::new, |values| values.split('\0').collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTS: &[Opt] = &[
+        Opt { names: &["-f", "--field-numbers"], value: Some("FIELDS") },
+        Opt { names: &["-F", "--fields"], value: Some("FIELDS") },
+        Opt { names: &["-d", "--delimiter"], value: Some("DELIMITER") },
+        Opt { names: &["--no-header"], value: None },
+    ];
+
+    fn parser() -> Parser {
+        Parser::new(OPTS).positional(&["file"]).exclusive(&[&["-f", "-F"]])
+    }
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_1() {
+        let actual = parser().parse(vec!["-F", "id", "--delimiter=,", "--no-header", "data.csv"]).unwrap();
+        assert_eq!(map(&[("-F", "id"), ("-d", ","), ("--no-header", "--no-header"), ("file", "data.csv")]), actual);
+        // 短いオプションに値を続けて指定する
+        let actual = parser().parse(vec!["-d,", "--field-numbers", "0,1"]).unwrap();
+        assert_eq!(map(&[("-d", ","), ("-f", "0,1")]), actual);
+        // 「--」以降は位置引数とみなす
+        let actual = parser().parse(vec!["-F", "-", "--", "--no-header"]).unwrap();
+        assert_eq!(map(&[("-F", "-"), ("file", "--no-header")]), actual);
+        // 「-」は標準入力を表す位置引数
+        let actual = parser().parse(vec!["-"]).unwrap();
+        assert_eq!(map(&[("file", "-")]), actual);
+    }
+
+    #[test]
+    fn test_parse_2() {
+        assert_eq!(Err(Error::Unknown("--field".to_string())), parser().parse(vec!["--field", "id"]));
+        assert_eq!(Err(Error::MissingValue("-F".to_string())), parser().parse(vec!["-F"]));
+        assert_eq!(Err(Error::UnexpectedValue("--no-header".to_string())), parser().parse(vec!["--no-header=1"]));
+        assert_eq!(Err(Error::Conflict("-f".to_string(), "-F".to_string())), parser().parse(vec!["-F", "id", "-f", "0"]));
+        assert_eq!(Err(Error::TooManyArguments("b.csv".to_string())), parser().parse(vec!["a.csv", "b.csv"]));
+        assert_eq!("-f と -F は同時に指定できません", Error::Conflict("-f".to_string(), "-F".to_string()).to_string());
+    }
+
+    #[test]
+    fn test_parse_until() {
+        const COMMON: &[Opt] = &[Opt { names: &["-h", "--help"], value: None }];
+        let parser = parser().with_opts(COMMON);
+        // 停止するフラグ以降の引数は検証しない
+        let options = parser.parse_until(vec!["-F", "id", "--help", "--unknown", "-f", "0"], &["-h"]).unwrap();
+        assert_eq!(map(&[("-F", "id"), ("-h", "-h")]), options);
+        // オプションの値と「--」以降の引数はフラグとみなさない
+        let options = parser.parse_until(vec!["-F", "-h", "--", "-h"], &["-h"]).unwrap();
+        assert_eq!(map(&[("-F", "-h"), ("file", "-h")]), options);
+    }
+
+    #[test]
+    fn test_parse_variadic() {
+        let parser = Parser::new(OPTS).positional(&["output", "files..."]);
+        let options = parser.parse(vec!["out.csv", "a.csv", "-F", "id", "-", "c.csv"]).unwrap();
+        assert_eq!("out.csv", options["output"]);
+        assert_eq!(vec!["a.csv", "-", "c.csv"], values(&options, "files"));
+        let options = parser.parse(vec!["out.csv"]).unwrap();
+        assert!(values(&options, "files").is_empty());
+    }
+
+    #[test]
+    fn test_help() {
+        let text = "OPTIONS:\n\n    -F:\n        出力するカラム\n    --no-header:\n        -F:\n";
+        let expected = "OPTIONS:\n\n    -F, --fields <FIELDS>:\n        出力するカラム\n    --no-header:\n        -F:\n";
+        assert_eq!(expected, parser().help(text));
+        assert_eq!(vec!["-F", "--no-header"], Parser::headings(text));
+    }
+
+    #[test]
+    fn test_with_opts() {
+        const COMMON: &[Opt] = &[Opt { names: &["-h", "--help"], value: None }];
+        let parser = parser().with_opts(COMMON);
+        assert_eq!(vec!["-f", "-F", "-d", "--no-header", "-h"], parser.names());
+        let options = parser.parse(vec!["--help", "-f", "0"]).unwrap();
+        assert!(parser.conflicts("-F", &options));
+        assert!(!parser.conflicts("-f", &options));
+        assert!(!parser.conflicts("-d", &options));
+    }
+}
@@ -0,0 +1,8 @@
+extern crate mtools;
+
+use mtools::cli;
+use std::env;
+
+fn main() {
+    cli::main(env::args().collect());
+}
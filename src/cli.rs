@@ -0,0 +1,353 @@
+//! mtools コマンドのサブコマンドの呼び出しと、各サブコマンドで共通のオプションの処理
+//!
+//! `mtools cut ...` のようにサブコマンド名を指定して呼び出すほか、
+//! busybox のように実行ファイル名(argv[0])が「m + サブコマンド名」の場合はそのサブコマンドを実行する。
+//! (mtools へのシンボリックリンク mcut は mtools cut と同じ動作をする)
+extern crate regex;
+
+pub mod agg;
+pub mod cat;
+pub mod count;
+pub mod cut;
+pub mod join;
+pub mod paste;
+pub mod sort;
+pub mod split;
+pub mod stats;
+pub mod transpose;
+
+use self::regex::Regex;
+use crate::args::{Opt, Parser};
+use crate::encoding::Encoding;
+use crate::mcut::{self, Header, Preamble};
+use crate::profile;
+use crate::util;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// サブコマンドの定義
+pub struct Command {
+    /// サブコマンド名
+    pub name: &'static str,
+    /// mtools --help に表示する説明
+    pub about: &'static str,
+    /// サブコマンドのエントリポイント。プログラム名とプログラム名を除いたコマンドライン引数を受け取る
+    pub run: fn(&str, Vec<String>),
+    /// サブコマンドのヘルプ(共通オプションの説明を除く)
+    pub help: &'static str,
+    /// サブコマンドのオプションの定義
+    pub parser: fn() -> Parser,
+}
+
+/// サブコマンドの一覧
+pub const COMMANDS: &[Command] = &[
+    Command { name: "agg", about: "カラムを指定して行をグループ化し、集計します。", run: agg::main, help: agg::HELP, parser: agg::parser },
+    Command { name: "cat", about: "ヘッダの異なる複数のファイルをカラム名で揃えて連結します。", run: cat::main, help: cat::HELP, parser: cat::parser },
+    Command { name: "count", about: "カラムの値ごとの出現回数を数えます。", run: count::main, help: count::HELP, parser: count::parser },
+    Command { name: "cut", about: "ファイルの選択したカラムを出力します。", run: cut::main, help: cut::HELP, parser: cut::parser },
+    Command { name: "join", about: "2つのファイルをキーで結合します。", run: join::main, help: join::HELP, parser: join::parser },
+    Command { name: "paste", about: "複数のファイルを行ごとに横に連結します。", run: paste::main, help: paste::HELP, parser: paste::parser },
+    Command { name: "sort", about: "カラムを指定して行を並べ替えます。", run: sort::main, help: sort::HELP, parser: sort::parser },
+    Command { name: "split", about: "カラムの値や行数でファイルを分割します。", run: split::main, help: split::HELP, parser: split::parser },
+    Command { name: "stats", about: "カラムごとの型や値の分布を集計します。", run: stats::main, help: stats::HELP, parser: stats::parser },
+    Command { name: "transpose", about: "ファイルの行と列を入れ替えます。", run: transpose::main, help: transpose::HELP, parser: transpose::parser },
+];
+
+/// 全てのサブコマンドで共通のオプションの定義
+pub const COMMON_OPTS: &[Opt] = &[
+    Opt { names: &["-d", "--delimiter"], value: Some("DELIMITER") },
+    Opt { names: &["--skip-lines"], value: Some("N") },
+    Opt { names: &["--header-regex"], value: Some("REGEX") },
+    Opt { names: &["--comment-prefix"], value: Some("PREFIX") },
+    Opt { names: &["--profile"], value: Some("NAME") },
+    Opt { names: &["--config"], value: Some("PATH") },
+    Opt { names: &["--encoding"], value: Some("ENCODING") },
+    Opt { names: &["-h", "--help"], value: None },
+    Opt { names: &["-V", "--version"], value: None },
+];
+
+/// 共通のオプションのヘルプ。各サブコマンドのヘルプの末尾に出力する
+pub const COMMON_HELP: &str = include_str!("resources/common.txt");
+
+const MTOOLS_HELP: &str = include_str!("resources/mtools.txt");
+
+/// コマンドライン引数に従ってサブコマンドを実行する
+///
+/// # Arguments
+/// * `args` - プログラム名(argv[0])を含むコマンドライン引数
+pub fn main(args: Vec<String>) {
+    let (program, command, start) = match resolve(&args) {
+        Some(resolved) => resolved,
+        None => {
+            let program = program_name(args.get(0).map(|arg| arg.as_str()).unwrap_or("mtools"));
+            match args.get(1).map(|arg| arg.as_str()) {
+                Some("-V") | Some("--version") => {
+                    println!("{} {}", program, env!("CARGO_PKG_VERSION"));
+                    process::exit(0);
+                }
+                None | Some("-h") | Some("--help") => help(),
+                Some(name) => {
+                    let names: Vec<&str> = COMMANDS.iter().map(|command| command.name).collect();
+                    let mut message = format!("不明なサブコマンド: {}", name);
+                    if let Some(similar) = names.iter().min_by_key(|e| util::edit_distance(name, e)) {
+                        if util::edit_distance(name, similar) <= 2 {
+                            message.push_str(&format!("\nもしかして: {}", similar));
+                        }
+                    }
+                    eprintln!("{}\n利用可能なサブコマンド: {}", message, names.join(", "));
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    (command.run)(&program, args.into_iter().skip(start).collect());
+}
+
+/// 実行するサブコマンドを解決する
+///
+/// 返り値は (エラーメッセージ等に表示するプログラム名, サブコマンド, サブコマンドに渡す引数の開始位置)。
+/// サブコマンドを解決できない場合はNoneを返す。
+///
+/// # Arguments
+/// * `args` - プログラム名(argv[0])を含むコマンドライン引数
+fn resolve(args: &[String]) -> Option<(String, &'static Command, usize)> {
+    let program = program_name(args.get(0)?);
+    if let Some(command) = COMMANDS.iter().find(|command| program == format!("m{}", command.name)) {
+        return Some((program, command, 1));
+    }
+    let name = args.get(1)?;
+    let command = COMMANDS.iter().find(|command| command.name == name)?;
+    Some((format!("{} {}", program, name), command, 2))
+}
+
+/// argv[0] からディレクトリと拡張子を除いたプログラム名を返す
+fn program_name(argv0: &str) -> String {
+    Path::new(argv0).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| argv0.to_string())
+}
+
+fn help() -> ! {
+    let mut text = String::from(MTOOLS_HELP);
+    for command in COMMANDS {
+        text.push_str(&format!("    {:<8}{}\n", command.name, command.about));
+    }
+    eprintln!("{}", text);
+    process::exit(1);
+}
+
+/// コマンドライン引数を解析し、プロファイルの適用と共通のオプションの検証を行う
+///
+/// --help, --version が指定されている場合はヘルプ, バージョンを出力して終了する。
+/// 解析に失敗した場合はエラーメッセージを出力して終了する。
+///
+/// # Arguments
+/// * `program` - プログラム名
+/// * `parser`  - サブコマンドのパーサ
+/// * `help`    - サブコマンドのヘルプの本文(共通のオプションのヘルプを除く)
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn parse_args(program: &str, parser: &Parser, help: &str, args: Vec<String>) -> HashMap<String, String> {
+    // --help, --version は以降のオプションの検証より優先する
+    let mut options = parser.parse_until(args, &["-h", "-V"]).unwrap_or_else(|e| {
+        eprintln!("{}\n詳しくは {} --help を参照してください。", e, program);
+        process::exit(1);
+    });
+    if options.contains_key("-h") {
+        eprintln!("{}", parser.help(&format!("{}\n\n{}", help, COMMON_HELP)));
+        process::exit(1);
+    }
+    if options.contains_key("-V") {
+        println!("{} {}", program, env!("CARGO_PKG_VERSION"));
+        process::exit(0);
+    }
+    apply_profile(parser, &mut options);
+    // 入力ファイルを開く前に文字コードを検証する
+    encoding(&options);
+    options
+}
+
+/// --profile オプションが指定されていれば設定ファイルのプロファイルをoptionsに追加する
+///
+/// コマンドライン引数で指定したオプションはプロファイルの値より優先する。
+/// また、コマンドライン引数で指定したオプションと同時に指定できないオプションはプロファイルから追加しない。
+///
+/// # Arguments
+/// * `parser`  - サブコマンドのパーサ
+/// * `options` - コマンドライン引数
+fn apply_profile(parser: &Parser, options: &mut HashMap<String, String>) {
+    let name = match options.get("--profile") {
+        Some(name) => name.clone(),
+        None       => return,
+    };
+    let paths = match options.get("--config") {
+        Some(path) => vec![PathBuf::from(path)],
+        None       => profile::default_paths(),
+    };
+    let profile = profile::load(&name, &paths).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let specified = options.clone();
+    for (key, value) in profile {
+        if parser.conflicts(&key, &specified) {
+            continue;
+        }
+        options.entry(key).or_insert(value);
+    }
+}
+
+/// 入力ファイルを開く。ファイルが指定されていないか「-」の場合は標準入力を返す
+///
+/// # Arguments
+/// * `options` - コマンドライン引数
+/// * `key`     - 入力ファイルを指定する位置引数の名前
+pub(crate) fn open_input(options: &HashMap<String, String>, key: &str) -> BufReader<Box<dyn Read>> {
+    open_file(options.get(key).map(|file| file.as_str()), options)
+}
+
+/// 入力ファイルを開く。ファイル名がNoneか「-」の場合は標準入力を返す
+///
+/// --encoding オプションを指定した場合は、その文字コードからUTF-8に変換しながら読み込む。
+///
+/// # Arguments
+/// * `file`    - ファイル名
+/// * `options` - コマンドライン引数
+pub(crate) fn open_file(file: Option<&str>, options: &HashMap<String, String>) -> BufReader<Box<dyn Read>> {
+    let reader: Box<dyn Read> = match file.filter(|file| *file != "-") {
+        Some(file) => Box::new(File::open(file).unwrap_or_else(|e| {
+            eprintln!("ファイルを開けません: {}\n{}", file, e);
+            process::exit(1);
+        })),
+        None => Box::new(io::stdin()),
+    };
+    BufReader::new(encoding(options).decode(reader))
+}
+
+/// --encoding オプションの文字コードを返す。(デフォルト値はUTF-8)
+///
+/// # Arguments
+/// * `options` - コマンドライン引数
+fn encoding(options: &HashMap<String, String>) -> Encoding {
+    match options.get("--encoding") {
+        Some(name) => Encoding::from_name(name).unwrap_or_else(|| {
+            eprintln!("未対応の文字コードです: {} (utf-8, utf-16, utf-16le, utf-16be のいずれかを指定してください)", name);
+            process::exit(1);
+        }),
+        None => Encoding::Utf8,
+    }
+}
+
+/// -d オプションの区切り文字を返す。(デフォルト値はタブ)
+///
+/// # Arguments
+/// * `options` - コマンドライン引数
+pub(crate) fn delimiter(options: &HashMap<String, String>) -> u8 {
+    match options.get("-d") {
+        Some(d) => {
+            let bytes: &[u8] = d.as_bytes();
+            if bytes.len() != 1 {
+                eprintln!("-d に指定できる文字はシングルバイト文字1文字のみです: {}", d);
+                process::exit(1);
+            }
+            bytes[0]
+        }
+        None => b'\t',
+    }
+}
+
+/// --skip-lines, --header-regex, --comment-prefix オプションに従ってヘッダ行を読み込む
+///
+/// ファイルが空の場合はNoneを返す。
+///
+/// # Arguments
+/// * `reader`  - 入力
+/// * `options` - コマンドライン引数
+pub(crate) fn read_header<R: BufRead>(reader: &mut R, options: &HashMap<String, String>) -> Option<Header> {
+    let preamble = Preamble {
+        skip_lines: usize_option(options, "--skip-lines").unwrap_or(0),
+        comment_prefix: options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec()),
+        header_pattern: options.get("--header-regex").map(|pattern| {
+            Regex::new(pattern).unwrap_or_else(|e| {
+                eprintln!("--header-regex に指定した正規表現が不正です: {}\n{}", pattern, e);
+                process::exit(1);
+            })
+        }),
+    };
+    mcut::read_header(reader, &preamble)
+}
+
+/// 0以上の整数を値に取るオプションを取得する
+///
+/// # Arguments
+/// * `options` - コマンドライン引数
+/// * `key`     - オプション名
+pub(crate) fn usize_option(options: &HashMap<String, String>, key: &str) -> Option<usize> {
+    options.get(key).map(|n| {
+        n.parse().unwrap_or_else(|_| {
+            eprintln!("{} には0以上の整数を指定してください: {}", key, n);
+            process::exit(1);
+        })
+    })
+}
+
+/// 「64M」のように単位(K, M, G)を付けて指定したバイト数を解釈する
+///
+/// # Arguments
+/// * `size` - バイト数
+pub(crate) fn parse_size(size: &str) -> Option<usize> {
+    let (number, unit) = match size.char_indices().last()? {
+        (i, 'K') | (i, 'k') => (&size[..i], 1 << 10),
+        (i, 'M') | (i, 'm') => (&size[..i], 1 << 20),
+        (i, 'G') | (i, 'g') => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    number.parse::<usize>().ok().and_then(|n| n.checked_mul(unit)).filter(|n| *n > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let (program, command, start) = resolve(&args(&["/usr/local/bin/mcut", "-F", "id"])).unwrap();
+        assert_eq!(("mcut", "cut", 1), (program.as_str(), command.name, start));
+        let (program, command, start) = resolve(&args(&["mtools", "cut", "-F", "id"])).unwrap();
+        assert_eq!(("mtools cut", "cut", 2), (program.as_str(), command.name, start));
+        let (program, _, _) = resolve(&args(&["mtools.exe", "cut"])).unwrap();
+        assert_eq!("mtools cut", program);
+        assert!(resolve(&args(&["mtools", "not_exists"])).is_none());
+        assert!(resolve(&args(&["mtools"])).is_none());
+        assert!(resolve(&args(&["mtools", "--help"])).is_none());
+    }
+
+    #[test]
+    fn test_help_in_sync() {
+        // 各サブコマンドのヘルプの見出し行とオプションの定義が一致していること
+        for command in COMMANDS {
+            let text = format!("{}\n\n{}", command.help, COMMON_HELP);
+            let mut headings = Parser::headings(&text);
+            let mut names = (command.parser)().names();
+            headings.sort();
+            names.sort();
+            assert_eq!(names, headings, "command: {}", command.name);
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(Some(100), parse_size("100"));
+        assert_eq!(Some(64 << 20), parse_size("64M"));
+        assert_eq!(Some(2 << 10), parse_size("2k"));
+        assert_eq!(None, parse_size("0"));
+        assert_eq!(None, parse_size("1.5G"));
+        assert_eq!(None, parse_size(""));
+    }
+}
@@ -0,0 +1,64 @@
+//! magg (mtools agg) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::magg;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process;
+
+/// magg のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-g", "--group-by"], value: Some("COLUMNS") },
+    Opt { names: &["-a", "--aggregate"], value: Some("AGGREGATES") },
+    Opt { names: &["--ignore-case"], value: None },
+    Opt { names: &["--max-groups"], value: Some("N") },
+    Opt { names: &["-T", "--temp-dir"], value: Some("DIR") },
+];
+
+pub const HELP: &str = include_str!("../resources/magg.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"])
+}
+
+/// magg のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let aggregates = options.get("-a").unwrap_or_else(|| {
+        eprintln!("-a で集計関数を指定してください。");
+        process::exit(1);
+    });
+    let group_by = options.get("-g").map(|group_by| group_by.as_str()).unwrap_or("");
+    let mut reader = cli::open_input(&options, "file");
+    let mut writer = BufWriter::new(io::stdout());
+    let delimiter = cli::delimiter(&options);
+    let header = match cli::read_header(&mut reader, &options) {
+        Some(header) => header,
+        None         => return,
+    };
+    let mut cfg = magg::Config::parse(&header.line, delimiter, group_by, aggregates, options.contains_key("--ignore-case"));
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    cfg.max_groups = cli::usize_option(&options, "--max-groups").map(|n| {
+        if n == 0 {
+            eprintln!("--max-groups には1以上の整数を指定してください: {}", n);
+            process::exit(1);
+        }
+        n
+    });
+    if let Some(dir) = options.get("-T") {
+        cfg.temp_dir = PathBuf::from(dir);
+    }
+    let result = cfg.write_header(&mut writer)
+        .and_then(|_| magg::magg(&mut reader, &mut writer, &cfg))
+        .and_then(|_| writer.flush());
+    if let Err(e) = result {
+        eprintln!("集計に失敗しました: {}", e);
+        process::exit(1);
+    }
+}
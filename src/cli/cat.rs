@@ -0,0 +1,78 @@
+//! mcat (mtools cat) コマンド
+use crate::args::{self, Opt, Parser};
+use crate::cli;
+use crate::mcat;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::process;
+
+/// mcat のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["--intersect"], value: None },
+    Opt { names: &["--default"], value: Some("VALUE") },
+    Opt { names: &["--source-column"], value: Some("NAME") },
+    Opt { names: &["--ignore-case"], value: None },
+];
+
+pub const HELP: &str = include_str!("../resources/mcat.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["files..."])
+}
+
+/// mcat のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let mut files = args::values(&options, "files");
+    if files.is_empty() {
+        files.push("-");
+    }
+    if files.iter().filter(|file| **file == "-").count() > 1 {
+        eprintln!("標準入力から読み込めるのは1つのファイルのみです。");
+        process::exit(1);
+    }
+    // 出力するカラムを決めるために、先に全てのファイルのヘッダ行を読み込む(空のファイルは読み飛ばす)
+    // 多数のファイルを同時に開かないよう、ファイルはヘッダ行を読み込んだら閉じ、出力する際に開き直す。
+    // 標準入力は開き直せないので、readerをそのまま保持する
+    let mut inputs = Vec::new();
+    let mut stdin = None;
+    for file in files {
+        let mut reader = cli::open_file(Some(file), &options);
+        if let Some(header) = cli::read_header(&mut reader, &options) {
+            if file == "-" {
+                stdin = Some(reader);
+            }
+            inputs.push((file, header.line));
+        }
+    }
+    let first_lines: Vec<&str> = inputs.iter().map(|(_, first_line)| first_line.as_str()).collect();
+    let mode = if options.contains_key("--intersect") { mcat::Mode::Intersection } else { mcat::Mode::Union };
+    let mut cfg = mcat::Config::new(&first_lines, cli::delimiter(&options), mode, options.contains_key("--ignore-case"));
+    cfg.default = options.get("--default").map_or_else(Vec::new, |default| default.as_bytes().to_vec());
+    cfg.source_column = options.get("--source-column").cloned();
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    let mut writer = BufWriter::new(io::stdout());
+    let mut result = cfg.write_header(&mut writer);
+    for (file, first_line) in &inputs {
+        if result.is_err() {
+            break;
+        }
+        let mut reader = if *file == "-" {
+            stdin.take().unwrap()
+        } else {
+            let mut reader = cli::open_file(Some(file), &options);
+            cli::read_header(&mut reader, &options);
+            reader
+        };
+        result = mcat::mcat(&mut reader, &mut writer, first_line, file, &cfg);
+    }
+    if let Err(e) = result.and_then(|_| writer.flush()) {
+        eprintln!("連結に失敗しました: {}", e);
+        process::exit(1);
+    }
+}
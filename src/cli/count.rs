@@ -0,0 +1,78 @@
+//! mcount (mtools count) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::mcount;
+use std::cmp;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::process;
+
+/// mcount のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-F", "--fields"], value: Some("FIELDS") },
+    Opt { names: &["--ignore-case"], value: None },
+    Opt { names: &["--top"], value: Some("N") },
+    Opt { names: &["--capacity"], value: Some("N") },
+];
+
+pub const HELP: &str = include_str!("../resources/mcount.txt");
+
+/// --top を指定した場合にメモリ上で数える値の種類の上限の、--top の件数に対する倍率
+const CAPACITY_FACTOR: usize = 100;
+
+/// --top を指定した場合にメモリ上で数える値の種類の上限の最小値
+const MIN_CAPACITY: usize = 10000;
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"])
+}
+
+/// mcount のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let fields = options.get("-F").unwrap_or_else(|| {
+        eprintln!("-F で値を数えるカラムを指定してください。");
+        process::exit(1);
+    });
+    let top = cli::usize_option(&options, "--top");
+    let capacity = match (cli::usize_option(&options, "--capacity"), top) {
+        (Some(capacity), _) => Some(capacity),
+        (None, Some(top))   => Some(cmp::max(top.saturating_mul(CAPACITY_FACTOR), MIN_CAPACITY)),
+        (None, None)        => None,
+    };
+    if let Some(capacity) = capacity {
+        if capacity < top.unwrap_or(1) || capacity == 0 {
+            eprintln!("--capacity には1以上かつ --top 以上の整数を指定してください: {}", capacity);
+            process::exit(1);
+        }
+    }
+    let mut reader = cli::open_input(&options, "file");
+    let mut writer = BufWriter::new(io::stdout());
+    let delimiter = cli::delimiter(&options);
+    let header = match cli::read_header(&mut reader, &options) {
+        Some(header) => header,
+        None         => return,
+    };
+    let mut cfg = mcount::Config::parse_fields(&header.line, delimiter, fields, options.contains_key("--ignore-case"));
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    cfg.top = top;
+    cfg.capacity = capacity;
+    let result = cfg.write_header(&mut writer)
+        .and_then(|_| mcount::mcount(&mut reader, &mut writer, &cfg))
+        .and_then(|approximate| writer.flush().map(|_| approximate));
+    match result {
+        Ok(true) => {
+            eprintln!("値の種類が {} を超えたため、出現回数は近似値です。(実際の出現回数以上の値になります)", cfg.capacity.unwrap());
+        }
+        Ok(false) => {}
+        Err(e) => {
+            eprintln!("集計に失敗しました: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! mcut (mtools cut) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::mcut;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::process;
+
+/// mcut のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-f", "--field-numbers"], value: Some("FIELDS") },
+    Opt { names: &["-F", "--fields"], value: Some("FIELDS") },
+    Opt { names: &["--no-header"], value: None },
+    Opt { names: &["--unique-header"], value: None },
+    Opt { names: &["--ignore-case"], value: None },
+    Opt { names: &["--pass-comments"], value: None },
+    Opt { names: &["--skip"], value: Some("N") },
+    Opt { names: &["--limit"], value: Some("N") },
+    Opt { names: &["--tail"], value: Some("N") },
+    Opt { names: &["--sample-rows"], value: Some("N") },
+    Opt { names: &["--sample-fraction"], value: Some("FRACTION") },
+    Opt { names: &["--seed"], value: Some("SEED") },
+    Opt { names: &["--explain"], value: None },
+    Opt { names: &["--explain-json"], value: None },
+    Opt { names: &["--list-columns"], value: None },
+    Opt { names: &["--sample-values"], value: Some("N") },
+];
+
+/// 同時に指定できないオプションのグループ
+const EXCLUSIVE: &[&[&str]] = &[
+    &["-f", "-F", "--list-columns"],
+    &["--explain", "--explain-json"],
+    &["--sample-rows", "--sample-fraction"],
+    &["--no-header", "--unique-header"],
+];
+
+pub const HELP: &str = include_str!("../resources/mcut.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"]).exclusive(EXCLUSIVE)
+}
+
+/// mcut のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let mut reader = cli::open_input(&options, "file");
+    let mut writer = BufWriter::new(io::stdout());
+    let delimiter = cli::delimiter(&options);
+
+    if let Some(header) = cli::read_header(&mut reader, &options) {
+        let line = header.line.clone();
+        if options.contains_key("--list-columns") {
+            // --list-columns オプション: カラムの一覧を出力する
+            let sample = cli::usize_option(&options, "--sample-values").unwrap_or(3);
+            let comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes());
+            mcut::list_columns(&mut reader, &mut writer, &header, delimiter, comment_prefix, sample);
+            return;
+        }
+        // カラム名とindexの対応表を作成
+        let filename = options.get("file").cloned().unwrap_or_else(|| String::from("-"));
+        if let Some(fields) = options.get("-f") {
+            // -f オプション: ヘッダを考慮しない
+            let mut cfg = mcut::Config::parse_field_as_number(line.clone(), delimiter, fields.clone());
+            header.apply(&mut cfg);
+            set_row_options(&options, &mut cfg, filename);
+            if explain(&options, &cfg, &mut writer) {
+                return;
+            }
+            // 1行目を出力する
+            cfg.write_first_line(&mut writer);
+            mcut::mcut(&mut reader, &mut writer, cfg);
+        } else if let Some(fields) = options.get("-F") {
+            // -F オプション: ヘッダを考慮する
+            let mut cfg = if options.contains_key("--ignore-case") {
+                mcut::Config::parse_field_as_name_ignore_case(line.clone(), delimiter, fields.clone())
+            } else {
+                mcut::Config::parse_field_as_name(line.clone(), delimiter, fields.clone())
+            };
+            header.apply(&mut cfg);
+            set_row_options(&options, &mut cfg, filename);
+            if explain(&options, &cfg, &mut writer) {
+                return;
+            }
+            if !options.contains_key("--no-header") {
+                // --no-headerオプションが指定されていなければ1行目を出力する
+                if options.contains_key("--unique-header") {
+                    cfg.write_unique_header(&mut writer);
+                } else {
+                    cfg.write_header(&mut writer);
+                }
+            }
+            mcut::mcut(&mut reader, &mut writer, cfg);
+        } else {
+            eprintln!("-f と -F 少なくともどちらか一方を指定してください。");
+            process::exit(1);
+        };
+    } else {
+        std::process::exit(0);
+    };
+}
+
+/// 行ごとの処理に関わるオプションをcfgに設定する
+///
+/// # Arguments
+/// * `options`  - コマンドライン引数
+/// * `cfg`      - オプションを設定するConfig
+/// * `filename` - @filename に出力するファイル名。標準入力の場合は「-」
+fn set_row_options(options: &HashMap<String, String>, cfg: &mut mcut::Config, filename: String) {
+    cfg.filename = filename;
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    cfg.pass_comments = options.contains_key("--pass-comments");
+    cfg.skip = cli::usize_option(options, "--skip").unwrap_or(0);
+    cfg.limit = cli::usize_option(options, "--limit");
+    cfg.tail = cli::usize_option(options, "--tail");
+    if let Some(n) = cli::usize_option(options, "--sample-rows") {
+        cfg.sample = Some(mcut::Sample::Rows(n));
+    } else if let Some(fraction) = options.get("--sample-fraction") {
+        match fraction.parse::<f64>() {
+            Ok(f) if 0.0 <= f && f <= 1.0 => cfg.sample = Some(mcut::Sample::Fraction(f)),
+            _ => {
+                eprintln!("--sample-fraction には0以上1以下の数値を指定してください: {}", fraction);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(seed) = options.get("--seed") {
+        cfg.seed = seed.parse().unwrap_or_else(|_| {
+            eprintln!("--seed には0以上の整数を指定してください: {}", seed);
+            process::exit(1);
+        });
+    }
+}
+
+/// --explain, --explain-json オプションが指定されていれば出力対象カラムの解決結果を出力する
+///
+/// # Arguments
+/// * `options` - コマンドライン引数
+/// * `cfg`     - 解決結果を出力するConfig
+/// * `writer`  - 解決結果を書き込むwriter
+fn explain<W: Write>(options: &HashMap<String, String>, cfg: &mcut::Config, writer: &mut W) -> bool {
+    if options.contains_key("--explain-json") {
+        cfg.write_explain_json(writer);
+        true
+    } else if options.contains_key("--explain") {
+        cfg.write_explain(writer);
+        true
+    } else {
+        false
+    }
+}
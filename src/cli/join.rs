@@ -0,0 +1,118 @@
+//! mjoin (mtools join) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::mjoin;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::process;
+
+/// mjoin のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-k", "--key"], value: Some("KEYS") },
+    Opt { names: &["--left-key"], value: Some("KEYS") },
+    Opt { names: &["--right-key"], value: Some("KEYS") },
+    Opt { names: &["-t", "--type"], value: Some("TYPE") },
+    Opt { names: &["-F", "--fields"], value: Some("FIELDS") },
+    Opt { names: &["--ignore-case"], value: None },
+    Opt { names: &["--sorted"], value: None },
+];
+
+pub const HELP: &str = include_str!("../resources/mjoin.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["left", "right"])
+}
+
+/// mjoin のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let (left_keys, right_keys) = match (options.get("-k"), options.get("--left-key"), options.get("--right-key")) {
+        (_, Some(left), Some(right)) => (left, right),
+        (Some(key), left, right)     => (left.unwrap_or(key), right.unwrap_or(key)),
+        _ => {
+            eprintln!("-k もしくは --left-key と --right-key で結合するキーを指定してください。");
+            process::exit(1);
+        }
+    };
+    let join_type = match options.get("-t") {
+        Some(name) => mjoin::JoinType::from_name(name).unwrap_or_else(|| {
+            eprintln!("-t には inner, left, right, full, semi, anti のいずれかを指定してください: {}", name);
+            process::exit(1);
+        }),
+        None => mjoin::JoinType::Inner,
+    };
+    if join_type.is_filter() && options.contains_key("-F") {
+        eprintln!("-F は -t semi, anti と同時に指定できません。");
+        process::exit(1);
+    }
+    // 結合の種類によらず、両方を標準入力から読み込むことはできない
+    let is_stdin = |key: &str| options.get(key).map_or(true, |file| file == "-");
+    if is_stdin("left") && is_stdin("right") {
+        eprintln!("標準入力から読み込めるのは一方のファイルのみです。");
+        process::exit(1);
+    }
+    // semi, anti は常に右側のファイルでキーの集合を構築する
+    let build_side = if join_type.is_filter() { mjoin::Side::Right } else { build_side(&options) };
+    let mut left = cli::open_input(&options, "left");
+    let mut right = cli::open_input(&options, "right");
+    let mut writer = BufWriter::new(io::stdout());
+    let delimiter = cli::delimiter(&options);
+    let left_header = read_header(&mut left, &options, "left");
+    let right_header = read_header(&mut right, &options, "right");
+    let ignore_case = options.contains_key("--ignore-case");
+    let mut cfg = mjoin::Config::new(&left_header, &right_header, delimiter, left_keys, right_keys, ignore_case);
+    cfg.join_type = join_type;
+    cfg.build_side = build_side;
+    if options.contains_key("--sorted") {
+        cfg.algorithm = mjoin::Algorithm::Merge;
+    }
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    if let Some(fields) = options.get("-F") {
+        cfg.set_fields(fields, ignore_case);
+    }
+    let result = cfg.write_header(&mut writer)
+        .and_then(|_| mjoin::mjoin(&mut left, &mut right, &mut writer, &cfg))
+        .and_then(|_| writer.flush());
+    if let Err(e) = result {
+        eprintln!("結合に失敗しました: {}", e);
+        process::exit(1);
+    }
+}
+
+/// ハッシュテーブルを構築する側を決める
+///
+/// 標準入力は1行ずつ読み込む側とし、両方ともファイルの場合はサイズの小さい方でハッシュテーブルを構築する。
+/// (両方とも標準入力の場合は呼び出し元でエラーにしている)
+///
+/// # Arguments
+/// * `options` - コマンドライン引数
+fn build_side(options: &HashMap<String, String>) -> mjoin::Side {
+    let size = |key: &str| -> Option<u64> {
+        options.get(key).filter(|file| *file != "-").and_then(|file| fs::metadata(file).ok()).map(|metadata| metadata.len())
+    };
+    match (size("left"), size("right")) {
+        (Some(left), Some(right)) if left < right => mjoin::Side::Left,
+        (Some(_), None) => mjoin::Side::Left,
+        _ => mjoin::Side::Right,
+    }
+}
+
+/// ヘッダ行を読み込む。結合するキーを解決できないので、ファイルが空の場合はエラーで終了する
+///
+/// # Arguments
+/// * `reader`  - 入力
+/// * `options` - コマンドライン引数
+/// * `key`     - ファイル名を格納した位置引数の名前
+fn read_header<R: BufRead>(reader: &mut R, options: &HashMap<String, String>, key: &str) -> String {
+    cli::read_header(reader, options).map(|header| header.line).unwrap_or_else(|| {
+        eprintln!("ヘッダのない空のファイルは結合できません: {}", options.get(key).map_or("-", |file| file.as_str()));
+        process::exit(1);
+    })
+}
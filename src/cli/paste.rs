@@ -0,0 +1,96 @@
+//! mpaste (mtools paste) コマンド
+use crate::args::{self, Opt, Parser};
+use crate::cli;
+use crate::mpaste;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+
+/// mpaste のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["--prefixes"], value: Some("PREFIXES") },
+    Opt { names: &["--pad"], value: None },
+];
+
+pub const HELP: &str = include_str!("../resources/mpaste.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["files..."])
+}
+
+/// mpaste のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let files = args::values(&options, "files");
+    if files.is_empty() {
+        eprintln!("連結するファイルを指定してください。");
+        process::exit(1);
+    }
+    if files.iter().filter(|file| **file == "-").count() > 1 {
+        eprintln!("標準入力から読み込めるのは1つのファイルのみです。");
+        process::exit(1);
+    }
+    let prefixes: Vec<String> = match options.get("--prefixes") {
+        Some(prefixes) => {
+            let prefixes: Vec<String> = prefixes.split(',').map(String::from).collect();
+            if prefixes.len() != files.len() {
+                eprintln!("--prefixes にはファイルと同じ数の接頭辞を指定してください: {}", prefixes.join(","));
+                process::exit(1);
+            }
+            prefixes
+        }
+        None => files.iter().map(|file| default_prefix(file)).collect(),
+    };
+    let mut readers = Vec::new();
+    let mut first_lines = Vec::new();
+    for file in &files {
+        let mut reader = cli::open_file(Some(file), &options);
+        // 空のファイルはヘッダのカラム数が分からず、行を揃えられないのでエラーにする
+        let header = cli::read_header(&mut reader, &options).unwrap_or_else(|| {
+            eprintln!("ヘッダのない空のファイルは連結できません: {}", file);
+            process::exit(1);
+        });
+        first_lines.push(header.line);
+        readers.push(reader);
+    }
+    let first_lines: Vec<&str> = first_lines.iter().map(|first_line| first_line.as_str()).collect();
+    let prefixes: Vec<&str> = prefixes.iter().map(|prefix| prefix.as_str()).collect();
+    let mut cfg = mpaste::Config::new(&first_lines, &prefixes, cli::delimiter(&options));
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    if options.contains_key("--pad") {
+        cfg.on_mismatch = mpaste::OnMismatch::Pad;
+    }
+    let mut writer = BufWriter::new(io::stdout());
+    let result = cfg.write_header(&mut writer)
+        .and_then(|_| mpaste::mpaste(&mut readers, &mut writer, &cfg))
+        .and_then(|_| writer.flush());
+    if let Err(e) = result {
+        eprintln!("連結に失敗しました: {}", e);
+        process::exit(1);
+    }
+}
+
+/// ファイル名からディレクトリと拡張子を除いた接頭辞を返す。標準入力の場合は「stdin」
+fn default_prefix(file: &str) -> String {
+    if file == "-" {
+        return String::from("stdin");
+    }
+    Path::new(file).file_stem().map_or_else(|| file.to_string(), |stem| stem.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_prefix() {
+        assert_eq!("features", default_prefix("data/features.csv"));
+        assert_eq!("stdin", default_prefix("-"));
+    }
+}
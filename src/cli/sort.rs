@@ -0,0 +1,60 @@
+//! msort (mtools sort) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::msort;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process;
+
+/// msort のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-k", "--key"], value: Some("KEYS") },
+    Opt { names: &["--ignore-case"], value: None },
+    Opt { names: &["-S", "--buffer-size"], value: Some("SIZE") },
+    Opt { names: &["-T", "--temp-dir"], value: Some("DIR") },
+];
+
+pub const HELP: &str = include_str!("../resources/msort.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"])
+}
+
+/// msort のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let keys = options.get("-k").unwrap_or_else(|| {
+        eprintln!("-k でソートキーを指定してください。");
+        process::exit(1);
+    });
+    let mut reader = cli::open_input(&options, "file");
+    let mut writer = BufWriter::new(io::stdout());
+    let delimiter = cli::delimiter(&options);
+    let header = match cli::read_header(&mut reader, &options) {
+        Some(header) => header,
+        None         => return,
+    };
+    let mut cfg = msort::Config::parse_keys(&header.line, delimiter, keys, options.contains_key("--ignore-case"));
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    if let Some(size) = options.get("-S") {
+        cfg.buffer_size = cli::parse_size(size).unwrap_or_else(|| {
+            eprintln!("-S には1以上のバイト数を指定してください (K, M, G の単位を指定できます): {}", size);
+            process::exit(1);
+        });
+    }
+    if let Some(dir) = options.get("-T") {
+        cfg.temp_dir = PathBuf::from(dir);
+    }
+    writeln!(writer, "{}", header.line).unwrap();
+    let result = msort::msort(&mut reader, &mut writer, &cfg).and_then(|_| writer.flush());
+    if let Err(e) = result {
+        eprintln!("ソートに失敗しました: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,83 @@
+//! msplit (mtools split) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::msplit;
+use std::process;
+
+/// msplit のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-k", "--key"], value: Some("KEYS") },
+    Opt { names: &["--rows"], value: Some("N") },
+    Opt { names: &["--bytes"], value: Some("SIZE") },
+    Opt { names: &["-o", "--output"], value: Some("TEMPLATE") },
+    Opt { names: &["--max-open-files"], value: Some("N") },
+    Opt { names: &["--ignore-case"], value: None },
+];
+
+/// 同時に指定できないオプションのグループ
+const EXCLUSIVE: &[&[&str]] = &[
+    &["-k", "--rows", "--bytes"],
+];
+
+pub const HELP: &str = include_str!("../resources/msplit.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"]).exclusive(EXCLUSIVE)
+}
+
+/// msplit のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let template = options.get("-o").map_or("{}.csv", |template| template.as_str());
+    if !template.contains("{}") {
+        eprintln!("-o の出力ファイル名には「{{}}」を含めてください: {}", template);
+        process::exit(1);
+    }
+    let mut reader = cli::open_input(&options, "file");
+    let delimiter = cli::delimiter(&options);
+    let header = match cli::read_header(&mut reader, &options) {
+        Some(header) => header,
+        None         => return,
+    };
+    let mode = if let Some(keys) = options.get("-k") {
+        msplit::Mode::keys(&header.line, delimiter, keys, options.contains_key("--ignore-case"))
+    } else if let Some(rows) = cli::usize_option(&options, "--rows") {
+        if rows == 0 {
+            eprintln!("--rows には1以上の整数を指定してください: {}", rows);
+            process::exit(1);
+        }
+        msplit::Mode::Rows(rows)
+    } else if let Some(size) = options.get("--bytes") {
+        msplit::Mode::Bytes(cli::parse_size(size).unwrap_or_else(|| {
+            eprintln!("--bytes には1以上のバイト数を指定してください (K, M, G の単位を指定できます): {}", size);
+            process::exit(1);
+        }))
+    } else {
+        eprintln!("-k, --rows, --bytes のいずれかで分割の方法を指定してください。");
+        process::exit(1);
+    };
+    let mut cfg = msplit::Config::new(delimiter, mode, template);
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    if let Some(n) = cli::usize_option(&options, "--max-open-files") {
+        if n == 0 {
+            eprintln!("--max-open-files には1以上の整数を指定してください: {}", n);
+            process::exit(1);
+        }
+        cfg.max_open_files = n;
+    }
+    match msplit::msplit(&mut reader, &header.line, &cfg) {
+        Ok(paths) => {
+            for path in paths {
+                println!("{}", path.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("分割に失敗しました: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! mstats (mtools stats) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::mstats;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::process;
+
+/// mstats のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["-F", "--fields"], value: Some("FIELDS") },
+    Opt { names: &["--ignore-case"], value: None },
+    Opt { names: &["--approx-distinct"], value: None },
+    Opt { names: &["--top"], value: Some("N") },
+    Opt { names: &["--json"], value: None },
+];
+
+pub const HELP: &str = include_str!("../resources/mstats.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"])
+}
+
+/// mstats のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let mut reader = cli::open_input(&options, "file");
+    let mut writer = BufWriter::new(io::stdout());
+    let delimiter = cli::delimiter(&options);
+    let header = match cli::read_header(&mut reader, &options) {
+        Some(header) => header,
+        None         => return,
+    };
+    let fields = options.get("-F").map(|fields| fields.as_str());
+    let mut cfg = mstats::Config::parse_fields(&header.line, delimiter, fields, options.contains_key("--ignore-case"));
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    cfg.approx_distinct = options.contains_key("--approx-distinct");
+    if let Some(top) = cli::usize_option(&options, "--top") {
+        cfg.top = top;
+    }
+    if options.contains_key("--json") {
+        cfg.format = mstats::Format::Json;
+    }
+    let result = mstats::mstats(&mut reader, &mut writer, &cfg).and_then(|_| writer.flush());
+    if let Err(e) = result {
+        eprintln!("集計に失敗しました: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,47 @@
+//! mtranspose (mtools transpose) コマンド
+use crate::args::{Opt, Parser};
+use crate::cli;
+use crate::mtranspose;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process;
+
+/// mtranspose のオプションの定義。ヘルプの見出し行はこの定義から生成する
+const OPTS: &[Opt] = &[
+    Opt { names: &["--low-memory"], value: None },
+    Opt { names: &["-T", "--temp-dir"], value: Some("DIR") },
+];
+
+pub const HELP: &str = include_str!("../resources/mtranspose.txt");
+
+pub fn parser() -> Parser {
+    Parser::new(OPTS).with_opts(cli::COMMON_OPTS).positional(&["file"])
+}
+
+/// mtranspose のエントリポイント
+///
+/// # Arguments
+/// * `program` - エラーメッセージ等に表示するプログラム名
+/// * `args`    - プログラム名を除いたコマンドライン引数
+pub fn main(program: &str, args: Vec<String>) {
+    let options = cli::parse_args(program, &parser(), HELP, args);
+    let mut reader = cli::open_input(&options, "file");
+    let mut writer = BufWriter::new(io::stdout());
+    let header = match cli::read_header(&mut reader, &options) {
+        Some(header) => header,
+        None         => return,
+    };
+    let mut cfg = mtranspose::Config::new(cli::delimiter(&options));
+    cfg.comment_prefix = options.get("--comment-prefix").map(|prefix| prefix.as_bytes().to_vec());
+    cfg.low_memory = options.contains_key("--low-memory");
+    if let Some(dir) = options.get("-T") {
+        cfg.temp_dir = PathBuf::from(dir);
+    }
+    let result = mtranspose::mtranspose(&mut reader, &mut writer, &header.line, &cfg).and_then(|_| writer.flush());
+    if let Err(e) = result {
+        eprintln!("行と列の入れ替えに失敗しました: {}", e);
+        process::exit(1);
+    }
+}
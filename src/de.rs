@@ -0,0 +1,370 @@
+//! Readerで読み込んだ行を構造体にデシリアライズする (serde feature)
+extern crate serde;
+
+use self::serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use crate::reader::{Reader, Record};
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+
+/// デシリアライズに失敗した場合のエラー
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error { message: msg.to_string() }
+    }
+}
+
+impl<'a> Record<'a> {
+    /// 行を構造体にデシリアライズする
+    ///
+    /// 構造体のフィールドはヘッダのカラム名で対応付ける。ヘッダがない場合はカラムの順番で対応付ける。
+    /// 重複したカラム名の2つ目以降は、-F オプションと同様に「カラム名#2」のような名前で対応付ける。
+    /// カラム数がヘッダより少ない行は、足りないカラムを空文字列とみなす。(Option のフィールドは None になる)
+    ///
+    /// ```
+    /// use mtools::reader::Reader;
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Pref<'a> {
+    ///     id: u32,
+    ///     title: &'a str,
+    /// }
+    ///
+    /// let mut reader = Reader::with_header("title,id\n東京都,1\n".as_bytes(), b',').unwrap();
+    /// let record = reader.read_record().unwrap().unwrap();
+    /// let pref: Pref = record.deserialize().unwrap();
+    /// assert_eq!((1, "東京都"), (pref.id, pref.title));
+    /// ```
+    pub fn deserialize<T: de::Deserialize<'a>>(&self) -> Result<T, Error> {
+        T::deserialize(RecordDeserializer { record: *self })
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    /// 1行読み込んで構造体にデシリアライズする。ファイルの末尾に達した場合はNoneを返す
+    ///
+    /// デシリアライズに失敗した場合は行番号を含んだ io::ErrorKind::InvalidData のエラーを返す。
+    pub fn deserialize<T: de::DeserializeOwned>(&mut self) -> io::Result<Option<T>> {
+        let result = match self.read_record()? {
+            Some(record) => record.deserialize().map(Some),
+            None         => return Ok(None),
+        };
+        result.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}行目: {}", self.line_no(), e)))
+    }
+}
+
+/// 1行全体のデシリアライザ
+struct RecordDeserializer<'a> {
+    record: Record<'a>,
+}
+
+impl<'de> de::Deserializer<'de> for RecordDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.record.header().is_some() {
+            self.deserialize_map(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let header = match self.record.header() {
+            Some(header) => header,
+            None         => return Err(de::Error::custom("ヘッダがないためカラム名で対応付けできません")),
+        };
+        // カラムが足りない行も、ヘッダの全てのカラムを対応付ける
+        visitor.visit_map(RecordAccess { record: self.record, header, idx: 0, len: header.len() })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _name: &'static str, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        if self.record.header().is_some() {
+            self.deserialize_map(visitor)
+        } else {
+            self.deserialize_seq(visitor)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(RecordAccess { record: self.record, header: &[], idx: 0, len: self.record.len() })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct enum identifier ignored_any
+    }
+}
+
+/// 行の各カラムを順番に返すSeqAccess, MapAccess
+struct RecordAccess<'a> {
+    record: Record<'a>,
+    header: &'a [String],
+    idx: usize,
+    len: usize,
+}
+
+impl<'a> RecordAccess<'a> {
+    /// idx番目のカラムを対応付ける名前を返す。重複したカラム名の2つ目以降は「カラム名#n」とする
+    fn key(&self, idx: usize) -> Cow<'a, str> {
+        let name = &self.header[idx];
+        let nth = self.header[..idx].iter().filter(|e| *e == name).count() + 1;
+        if nth == 1 {
+            Cow::Borrowed(name)
+        } else {
+            Cow::Owned(format!("{}#{}", name, nth))
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for RecordAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.idx >= self.len {
+            return Ok(None);
+        }
+        let value = self.record.get(self.idx).unwrap();
+        self.idx += 1;
+        seed.deserialize(FieldDeserializer { value, idx: self.idx - 1 }).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.idx)
+    }
+}
+
+impl<'de> de::MapAccess<'de> for RecordAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.idx >= self.len {
+            return Ok(None);
+        }
+        seed.deserialize(self.key(self.idx).into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        // カラムが足りない行は空文字列とみなす
+        let value = self.record.field(self.idx);
+        self.idx += 1;
+        seed.deserialize(FieldDeserializer { value, idx: self.idx - 1 })
+            .map_err(|e| de::Error::custom(format!("{}: {}", self.key(self.idx - 1), e)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.idx)
+    }
+}
+
+/// 1カラムの値のデシリアライザ
+struct FieldDeserializer<'a> {
+    value: &'a [u8],
+    idx: usize,
+}
+
+impl<'a> FieldDeserializer<'a> {
+    fn to_str(&self) -> Result<&'a str, Error> {
+        std::str::from_utf8(self.value)
+            .map_err(|_| de::Error::custom(format!("{}番目のカラムがUTF-8の文字列ではありません", self.idx)))
+    }
+
+    fn parse<T: std::str::FromStr>(&self, type_name: &str) -> Result<T, Error> {
+        let s = self.to_str()?;
+        s.trim().parse::<T>().map_err(|_| de::Error::custom(format!("{} に変換できません: {}", type_name, s)))
+    }
+}
+
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident: $ty:ty,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse::<$ty>(stringify!($ty))?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for FieldDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.to_str()?)
+    }
+
+    deserialize_parse! {
+        deserialize_bool => visit_bool: bool,
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+        deserialize_char => visit_char: char,
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.value)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.value)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // 空文字列はNoneとみなす
+        if self.value.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        // ユニットバリアントのみ対応する
+        visitor.visit_enum(self.to_str()?.into_deserializer())
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        str string unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum Region {
+        #[serde(rename = "関東")]
+        Kanto,
+        #[serde(rename = "関西")]
+        Kansai,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Pref<'a> {
+        id: u32,
+        title: &'a str,
+        kana: Option<String>,
+        narrow1: Option<Region>,
+    }
+
+    #[test]
+    fn test_deserialize_1() {
+        let data = "id,title,kana,narrow1\n01,東京都,トウキョウト,関東\n02,大阪府,,\n03,茨城県,,関西\n";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+        let record = reader.read_record().unwrap().unwrap();
+        let expected = Pref { id: 1, title: "東京都", kana: Some(String::from("トウキョウト")), narrow1: Some(Region::Kanto) };
+        assert_eq!(expected, record.deserialize().unwrap());
+        let record = reader.read_record().unwrap().unwrap();
+        let expected = Pref { id: 2, title: "大阪府", kana: None, narrow1: None };
+        assert_eq!(expected, record.deserialize().unwrap());
+        let record = reader.read_record().unwrap().unwrap();
+        let actual: (u8, String) = record.deserialize().unwrap();
+        assert_eq!((3, String::from("茨城県")), actual);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Item {
+        id: u32,
+        price: f64,
+    }
+
+    #[test]
+    fn test_deserialize_2() {
+        let data = "price,name,id\n100.5,foo,1\nabc,bar,2\n";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+        assert_eq!(Some(Item { id: 1, price: 100.5 }), reader.deserialize().unwrap());
+        let err = reader.deserialize::<Item>().unwrap_err();
+        assert_eq!("3行目: price: f64 に変換できません: abc", err.to_string());
+        assert_eq!(None, reader.deserialize::<Item>().unwrap());
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Price {
+        id: u32,
+        price: Option<f64>,
+        #[serde(rename = "price#2")]
+        sale_price: Option<f64>,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn test_deserialize_short_row() {
+        // カラムが足りない行は空文字列とみなし、Option のフィールドは None になる
+        let data = "id,price,price,note\n1,100,80,セール\n2,120\n3\n";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+        let expected = Price { id: 1, price: Some(100.0), sale_price: Some(80.0), note: Some(String::from("セール")) };
+        assert_eq!(Some(expected), reader.deserialize().unwrap());
+        assert_eq!(Some(Price { id: 2, price: Some(120.0), sale_price: None, note: None }), reader.deserialize().unwrap());
+        assert_eq!(Some(Price { id: 3, price: None, sale_price: None, note: None }), reader.deserialize().unwrap());
+    }
+
+    #[test]
+    fn test_deserialize_duplicate_names() {
+        // 重複したカラム名の2つ目以降は「カラム名#n」で対応付ける
+        let data = "id,price,price,price\n1,100,abc,80\n";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+        let record = reader.read_record().unwrap().unwrap();
+        let err = record.deserialize::<Price>().unwrap_err();
+        assert_eq!("price#2: f64 に変換できません: abc", err.to_string());
+        let actual: std::collections::HashMap<String, String> = record.deserialize().unwrap();
+        assert_eq!(Some("80"), actual.get("price#3").map(|e| e.as_str()));
+    }
+
+    #[test]
+    fn test_deserialize_3() {
+        // ヘッダなしの場合はカラムの順番で対応付ける
+        let mut reader = Reader::new("1\t2.5\n".as_bytes(), b'\t');
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(Item { id: 1, price: 2.5 }, record.deserialize().unwrap());
+    }
+}
@@ -0,0 +1,175 @@
+//! 入力ファイルの文字コードをUTF-8に変換する
+use std::char;
+use std::io;
+use std::io::prelude::*;
+
+/// 一度に読み込むバイト数
+const CHUNK_SIZE: usize = 8192;
+
+/// 入力ファイルの文字コード
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    /// UTF-16。BOMがあればBOMに従い、なければリトルエンディアンとみなす
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// 文字コード名からEncodingを返す。大文字小文字と「-」「_」は区別しない
+    ///
+    /// # Arguments
+    /// * `name` - 文字コード名。例) utf-8, UTF-16LE
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace("-", "").replace("_", "").as_str() {
+            "utf8"    => Some(Encoding::Utf8),
+            "utf16"   => Some(Encoding::Utf16),
+            "utf16le" => Some(Encoding::Utf16Le),
+            "utf16be" => Some(Encoding::Utf16Be),
+            _         => None,
+        }
+    }
+
+    /// readerから読み込んだバイト列をこの文字コードからUTF-8に変換するreaderを返す
+    ///
+    /// # Arguments
+    /// * `reader` - 入力
+    pub fn decode<'a, R: Read + 'a>(self, reader: R) -> Box<dyn Read + 'a> {
+        match self {
+            Encoding::Utf8    => Box::new(reader),
+            Encoding::Utf16   => Box::new(Utf16Reader::new(reader, None)),
+            Encoding::Utf16Le => Box::new(Utf16Reader::new(reader, Some(false))),
+            Encoding::Utf16Be => Box::new(Utf16Reader::new(reader, Some(true))),
+        }
+    }
+}
+
+/// UTF-16のバイト列をUTF-8に変換しながら読み込むreader
+///
+/// 不正なサロゲートペアと末尾の半端なバイトは U+FFFD に置き換える。
+struct Utf16Reader<R> {
+    reader: R,
+    /// ビッグエンディアンかどうか。Noneの場合は先頭のBOMで判定する
+    big_endian: Option<bool>,
+    /// 変換していない入力のバイト列
+    input: Vec<u8>,
+    /// 変換済みで読み出していないUTF-8のバイト列
+    output: Vec<u8>,
+    /// output の読み出し済みの位置
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> Utf16Reader<R> {
+    fn new(reader: R, big_endian: Option<bool>) -> Self {
+        Utf16Reader { reader, big_endian, input: Vec::new(), output: Vec::new(), pos: 0, eof: false }
+    }
+
+    /// 入力を読み込んで output に変換する
+    fn fill(&mut self) -> io::Result<()> {
+        self.output.clear();
+        self.pos = 0;
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let n = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        self.input.extend_from_slice(&chunk[..n]);
+        if n == 0 {
+            self.eof = true;
+        }
+        let big_endian = match self.big_endian {
+            Some(big_endian) => big_endian,
+            None if self.input.len() < 2 && !self.eof => return Ok(()),
+            None => {
+                let big_endian = self.input.starts_with(&[0xFE, 0xFF]);
+                if big_endian || self.input.starts_with(&[0xFF, 0xFE]) {
+                    self.input.drain(..2);
+                }
+                self.big_endian = Some(big_endian);
+                big_endian
+            }
+        };
+        let units: Vec<u16> = self.input.chunks_exact(2)
+            .map(|pair| if big_endian { u16::from_be_bytes([pair[0], pair[1]]) } else { u16::from_le_bytes([pair[0], pair[1]]) })
+            .collect();
+        let mut count = units.len();
+        // サロゲートペアの前半で終わっている場合は、後半を読み込んでから変換する
+        if !self.eof && units.last().map_or(false, |unit| (0xD800..0xDC00).contains(unit)) {
+            count -= 1;
+        }
+        let mut buf = [0u8; 4];
+        for c in char::decode_utf16(units[..count].iter().cloned()) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        self.input.drain(..count * 2);
+        if self.eof && !self.input.is_empty() {
+            self.output.extend_from_slice(char::REPLACEMENT_CHARACTER.encode_utf8(&mut buf).as_bytes());
+            self.input.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Utf16Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.output.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = std::cmp::min(buf.len(), self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoding: Encoding, bytes: &[u8]) -> String {
+        let mut ret = String::new();
+        encoding.decode(bytes).read_to_string(&mut ret).unwrap();
+        ret
+    }
+
+    fn utf16(s: &str, big_endian: bool) -> Vec<u8> {
+        s.encode_utf16().flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() }.to_vec()).collect()
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Some(Encoding::Utf8), Encoding::from_name("UTF-8"));
+        assert_eq!(Some(Encoding::Utf16Le), Encoding::from_name("utf-16le"));
+        assert_eq!(Some(Encoding::Utf16Be), Encoding::from_name("UTF_16BE"));
+        assert_eq!(None, Encoding::from_name("shift_jis"));
+    }
+
+    #[test]
+    fn test_decode_utf16() {
+        let text = "id\ttitle\n1\t東京都🗼\n";
+        assert_eq!(text, decode(Encoding::Utf16Le, &utf16(text, false)));
+        assert_eq!(text, decode(Encoding::Utf16Be, &utf16(text, true)));
+        // BOMで判定する。BOMがなければリトルエンディアンとみなす
+        assert_eq!(text, decode(Encoding::Utf16, &[&[0xFE, 0xFF][..], &utf16(text, true)[..]].concat()));
+        assert_eq!(text, decode(Encoding::Utf16, &[&[0xFF, 0xFE][..], &utf16(text, false)[..]].concat()));
+        assert_eq!(text, decode(Encoding::Utf16, &utf16(text, false)));
+        // 不正なサロゲートと末尾の半端なバイトは U+FFFD に置き換える
+        assert_eq!("a\u{FFFD}b\u{FFFD}", decode(Encoding::Utf16Le, &[b'a', 0, 0x00, 0xD8, b'b', 0, 0x41]));
+    }
+
+    #[test]
+    fn test_decode_utf16_chunk_boundary() {
+        // サロゲートペアが CHUNK_SIZE の境界をまたぐ場合
+        let text = format!("{}🗼", "a".repeat(CHUNK_SIZE / 2 - 1));
+        assert_eq!(text, decode(Encoding::Utf16Le, &utf16(&text, false)));
+    }
+}
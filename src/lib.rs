@@ -1,2 +1,20 @@
+pub mod args;
+pub mod cli;
+pub mod encoding;
+pub mod magg;
+pub mod mcat;
+pub mod mcount;
 pub mod mcut;
+pub mod mjoin;
+pub mod mpaste;
+pub mod msort;
+pub mod msplit;
+pub mod mstats;
+pub mod mtranspose;
+pub mod profile;
+pub mod reader;
 pub mod util;
+#[cfg(feature = "serde")]
+pub mod de;
+#[cfg(feature = "serde")]
+pub mod ser;
@@ -0,0 +1,521 @@
+//! ヘッダのカラム名で指定したカラムでグループ化して集計する
+use crate::mcut;
+use crate::reader::{Reader, Record};
+use crate::util;
+use crate::util::TempDir;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+
+/// グループ数が上限を超えた場合に、メモリ上にないグループの行を書き出す一時ファイルの数
+const PARTITIONS: usize = 16;
+
+/// 集計関数
+#[derive(Debug, PartialEq, Clone)]
+pub enum Func {
+    /// 行数。カラムを指定した場合は空文字列でない値の数
+    Count,
+    /// 合計
+    Sum,
+    /// 平均
+    Avg,
+    /// 最小値
+    Min,
+    /// 最大値
+    Max,
+    /// 重複を除いた値の数
+    CountDistinct,
+    /// 最初の行の値
+    First,
+    /// 最後の行の値
+    Last,
+    /// 値を区切り文字で連結した文字列
+    Concat(Vec<u8>),
+}
+
+impl Func {
+    fn name(&self) -> &'static str {
+        match self {
+            Func::Count         => "count",
+            Func::Sum           => "sum",
+            Func::Avg           => "avg",
+            Func::Min           => "min",
+            Func::Max           => "max",
+            Func::CountDistinct => "count_distinct",
+            Func::First         => "first",
+            Func::Last          => "last",
+            Func::Concat(_)     => "concat",
+        }
+    }
+}
+
+/// 出力する集計値
+#[derive(Debug, PartialEq)]
+pub struct Aggregate {
+    pub func: Func,
+    /// 集計する0から始まるカラム番号。count() の場合はNone
+    pub idx: Option<usize>,
+    /// 出力するカラム名
+    pub name: Vec<u8>,
+}
+
+/// 集計の設定
+pub struct Config {
+    pub delimiter: u8,
+    /// グループ化するカラムの0から始まるカラム番号
+    pub group_by: Vec<usize>,
+    /// グループ化するカラムのカラム名
+    pub group_names: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+    /// メモリ上で集計するグループ数の上限(1以上)。超えた場合は新しいグループの行を一時ファイルに書き出して後で集計する
+    pub max_groups: Option<usize>,
+    /// 一時ファイルを作成するディレクトリ
+    pub temp_dir: PathBuf,
+    /// この文字列で始まる行はコメント行とみなし、集計しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// ヘッダ行とグループ化するカラム、集計関数の指定からConfigを生成する
+    ///
+    /// 集計関数は「関数名(カラム名)」をカンマ区切りで指定する。「名前=関数名(カラム名)」の形式で出力するカラム名を指定できる。
+    /// concat は「concat(カラム名,'区切り文字')」の形式で区切り文字を指定できる。(デフォルト値は「;」)
+    ///
+    /// # Arguments
+    /// * `first_line`  - ヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `group_by`    - グループ化するカラム名のカンマ区切り。空文字列の場合は全ての行を1つのグループとして集計する
+    /// * `aggregates`  - 集計関数の指定。例) count(),total=sum(price),concat(title,'|')
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn parse(first_line: &str, delimiter: u8, group_by: &str, aggregates: &str, ignore_case: bool) -> Self {
+        let header = mcut::Config::split_header(first_line, delimiter);
+        let group_by: Vec<usize> = group_by.split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| Self::find_column(name, &header, ignore_case))
+            .collect();
+        let group_names = group_by.iter().map(|idx| header[*idx].to_string()).collect();
+        let aggregates = split_specs(aggregates).iter().map(|spec| Self::parse_aggregate(spec, &header, ignore_case)).collect();
+        Config {
+            delimiter,
+            group_by,
+            group_names,
+            aggregates,
+            max_groups: None,
+            temp_dir: env::temp_dir(),
+            comment_prefix: None,
+        }
+    }
+
+    fn find_column(col_name: &str, header: &[&str], ignore_case: bool) -> usize {
+        mcut::Config::find_column(col_name, header, ignore_case).unwrap_or_else(|| {
+            panic!("不明なフィールド: {}{}", col_name, mcut::Config::unknown_field_hint(col_name, header));
+        })
+    }
+
+    fn parse_aggregate(spec: &str, header: &[&str], ignore_case: bool) -> Aggregate {
+        let (alias, call) = match (spec.find('='), spec.find('(')) {
+            (Some(i), Some(j)) if i < j => (Some(&spec[..i]), &spec[i + 1..]),
+            _ => (None, spec),
+        };
+        let open = call.find('(').unwrap_or_else(|| invalid_spec(spec));
+        if !call.ends_with(')') {
+            invalid_spec(spec);
+        }
+        let func_name = &call[..open];
+        let mut args = call[open + 1..call.len() - 1].splitn(2, ',');
+        let col_name = args.next().unwrap();
+        let separator = args.next().map(|separator| {
+            let separator = separator.trim();
+            if separator.len() >= 2 && separator.starts_with('\'') && separator.ends_with('\'') {
+                separator[1..separator.len() - 1].as_bytes().to_vec()
+            } else {
+                invalid_spec(spec)
+            }
+        });
+        let func = match func_name {
+            "count"          => Func::Count,
+            "sum"            => Func::Sum,
+            "avg"            => Func::Avg,
+            "min"            => Func::Min,
+            "max"            => Func::Max,
+            "count_distinct" => Func::CountDistinct,
+            "first"          => Func::First,
+            "last"           => Func::Last,
+            "concat"         => Func::Concat(separator.clone().unwrap_or_else(|| b";".to_vec())),
+            _ => panic!("不明な集計関数: {} (count, sum, avg, min, max, count_distinct, first, last, concat のいずれかを指定してください)", func_name),
+        };
+        if separator.is_some() && func.name() != "concat" {
+            invalid_spec(spec);
+        }
+        let idx = match (&func, col_name) {
+            (Func::Count, "") => None,
+            (_, "")           => invalid_spec(spec),
+            (_, col_name)     => Some(Self::find_column(col_name, header, ignore_case)),
+        };
+        let name = match (alias, idx) {
+            (Some(alias), _) => alias.to_string(),
+            (None, Some(idx)) => format!("{}({})", func.name(), header[idx]),
+            (None, None)      => format!("{}()", func.name()),
+        };
+        Aggregate { func, idx, name: name.into_bytes() }
+    }
+
+    /// グループ化するカラムと集計値のカラム名をヘッダとして出力する
+    ///
+    /// # Arguments
+    /// * `writer` - ヘッダ行を書き込むwriter
+    pub fn write_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let names: Vec<&[u8]> = self.group_names.iter().map(|name| name.as_bytes())
+            .chain(self.aggregates.iter().map(|aggregate| &aggregate.name[..]))
+            .collect();
+        writer.write_all(&names.join(&self.delimiter))?;
+        writer.write_all(b"\n")
+    }
+
+    /// グループ化するカラムの値を区切り文字で連結したキーを返す。カラムが足りない行は空文字列とみなす
+    fn key(&self, record: &Record) -> Vec<u8> {
+        let values: Vec<&[u8]> = self.group_by.iter().map(|idx| record.field(*idx)).collect();
+        values.join(&self.delimiter)
+    }
+}
+
+fn invalid_spec(spec: &str) -> ! {
+    panic!("集計関数の指定が不正です: {} (例: sum(price))", spec);
+}
+
+/// カンマ区切りの集計関数の指定を分割する。括弧とシングルクォートの中のカンマでは分割しない
+fn split_specs(specs: &str) -> Vec<&str> {
+    let mut ret = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (i, c) in specs.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                ret.push(&specs[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    ret.push(&specs[start..]);
+    ret.into_iter().filter(|spec| !spec.is_empty()).collect()
+}
+
+fn parse_number(value: &[u8]) -> Option<f64> {
+    std::str::from_utf8(value).ok().and_then(|s| s.trim().parse::<f64>().ok()).filter(|n| !n.is_nan())
+}
+
+/// 2つの値を比較する。両方とも数値として解釈できる場合は数値として比較し、それ以外はバイト列として比較する
+fn compare_values(a: &[u8], b: &[u8]) -> Ordering {
+    match (parse_number(a), parse_number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _                  => a.cmp(b),
+    }
+}
+
+/// グループごとの集計の途中の値
+enum State {
+    Count(u64),
+    Sum(f64),
+    Avg(f64, u64),
+    Extreme(Option<Vec<u8>>),
+    Distinct(HashSet<Vec<u8>>),
+    Value(Option<Vec<u8>>),
+    Concat(Option<Vec<u8>>),
+}
+
+impl State {
+    fn new(aggregate: &Aggregate) -> Self {
+        match aggregate.func {
+            Func::Count                 => State::Count(0),
+            Func::Sum                   => State::Sum(0.0),
+            Func::Avg                   => State::Avg(0.0, 0),
+            Func::Min | Func::Max       => State::Extreme(None),
+            Func::CountDistinct         => State::Distinct(HashSet::new()),
+            Func::First | Func::Last    => State::Value(None),
+            Func::Concat(_)             => State::Concat(None),
+        }
+    }
+
+    /// 1行の値を集計する。カラムが足りない行は空文字列とみなす
+    fn update(&mut self, aggregate: &Aggregate, record: &Record) -> io::Result<()> {
+        let value = aggregate.idx.map(|idx| record.field(idx));
+        // count(), first, last, concat 以外は空文字列を集計しない
+        let is_empty = value.map_or(false, |value| value.is_empty());
+        let value = value.unwrap_or(b"");
+        let number = || parse_number(value).ok_or_else(|| {
+            let message = format!("{} の値を数値として解釈できません: {}", String::from_utf8_lossy(&aggregate.name), String::from_utf8_lossy(value));
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        });
+        match (self, &aggregate.func) {
+            (State::Count(n), _) => {
+                if !is_empty {
+                    *n += 1;
+                }
+            }
+            (State::Sum(sum), _) => {
+                if !is_empty {
+                    *sum += number()?;
+                }
+            }
+            (State::Avg(sum, n), _) => {
+                if !is_empty {
+                    *sum += number()?;
+                    *n += 1;
+                }
+            }
+            (State::Extreme(current), func) => {
+                let ord = if *func == Func::Min { Ordering::Less } else { Ordering::Greater };
+                if !is_empty && current.as_ref().map_or(true, |current| compare_values(value, current) == ord) {
+                    *current = Some(value.to_vec());
+                }
+            }
+            (State::Distinct(values), _) => {
+                if !is_empty && !values.contains(value) {
+                    values.insert(value.to_vec());
+                }
+            }
+            (State::Value(current), func) => {
+                if current.is_none() || *func == Func::Last {
+                    *current = Some(value.to_vec());
+                }
+            }
+            (State::Concat(current), Func::Concat(separator)) => {
+                match current {
+                    Some(current) => {
+                        current.extend_from_slice(separator);
+                        current.extend_from_slice(value);
+                    }
+                    None => *current = Some(value.to_vec()),
+                }
+            }
+            (State::Concat(_), _) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// 集計値を出力する
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            State::Count(n)     => write!(writer, "{}", n),
+            State::Sum(sum)     => write!(writer, "{}", sum),
+            State::Avg(_, 0)    => Ok(()),
+            State::Avg(sum, n)  => write!(writer, "{}", sum / *n as f64),
+            State::Distinct(values) => write!(writer, "{}", values.len()),
+            State::Extreme(value) | State::Value(value) | State::Concat(value) => {
+                writer.write_all(value.as_ref().map_or(b"", |value| &value[..]))
+            }
+        }
+    }
+}
+
+/// readerから読み込んだ行をcfgのグループ化するカラムでグループ化して集計し、writerに書き込む
+///
+/// 出力順はグループが最初に現れた順番となる。
+/// ただし cfg.max_groups を超えるグループがある場合は、メモリ上のグループを先に出力し、残りのグループは一時ファイルから読み込んで集計するので、
+/// 一時ファイルに書き出したグループの出力順はグループが最初に現れた順番とはならない。
+/// cfg.max_groups が Some(0) の場合は io::ErrorKind::InvalidInput のエラーを返す。
+///
+/// # Arguments
+/// * `reader` - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `cfg`    - グループ化するカラムや集計関数の設定
+pub fn magg<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, cfg: &Config) -> io::Result<()> {
+    if cfg.max_groups == Some(0) {
+        // 1つもグループを集計できず、一時ファイルに書き出しても同じ行を繰り返し書き出すことになる
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "max_groups には1以上の値を指定してください"));
+    }
+    let mut spill = Spill { temp_dir: None, seq: 0 };
+    aggregate(reader, writer, cfg, &mut spill, 0)
+}
+
+/// メモリ上で集計できないグループの行を書き出す一時ファイル
+struct Spill {
+    temp_dir: Option<TempDir>,
+    /// 一時ファイルの連番
+    seq: usize,
+}
+
+impl Spill {
+    fn create(&mut self, cfg: &Config) -> io::Result<(PathBuf, BufWriter<File>)> {
+        let dir = match self.temp_dir {
+            Some(ref dir) => dir,
+            None          => self.temp_dir.get_or_insert(TempDir::new(&cfg.temp_dir, "agg")?),
+        };
+        let path = dir.path.join(format!("part-{}", self.seq));
+        self.seq += 1;
+        let writer = BufWriter::new(File::create(&path)?);
+        Ok((path, writer))
+    }
+}
+
+/// 1回分の集計を行う。メモリ上にないグループの行はキーのハッシュ値で一時ファイルに振り分け、後で一時ファイルごとに集計する
+///
+/// # Arguments
+/// * `level` - 一時ファイルの再帰の深さ。深さごとに異なるハッシュ値で振り分ける
+fn aggregate<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, cfg: &Config, spill: &mut Spill, level: usize) -> io::Result<()> {
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let mut indices: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<u8>, Vec<State>)> = Vec::new();
+    let mut partitions: Vec<(PathBuf, BufWriter<File>)> = Vec::new();
+    if cfg.group_by.is_empty() && level == 0 {
+        // グループ化しない場合は行がなくても1行出力する
+        indices.insert(Vec::new(), 0);
+        groups.push((Vec::new(), cfg.aggregates.iter().map(State::new).collect()));
+    }
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        let key = cfg.key(&record);
+        let i = match indices.get(&key) {
+            Some(i) => *i,
+            None if cfg.max_groups.map_or(true, |max_groups| groups.len() < max_groups) => {
+                indices.insert(key.clone(), groups.len());
+                groups.push((key, cfg.aggregates.iter().map(State::new).collect()));
+                groups.len() - 1
+            }
+            None => {
+                if partitions.is_empty() {
+                    for _ in 0..PARTITIONS {
+                        partitions.push(spill.create(cfg)?);
+                    }
+                }
+                let mut hashed = key;
+                hashed.push(level as u8);
+                let (_, partition) = &mut partitions[(util::fnv1a64(&hashed) % PARTITIONS as u64) as usize];
+                partition.write_all(record.as_bytes())?;
+                partition.write_all(b"\n")?;
+                continue;
+            }
+        };
+        for (state, aggregate) in groups[i].1.iter_mut().zip(&cfg.aggregates) {
+            state.update(aggregate, &record)?;
+        }
+    }
+    for (key, states) in &groups {
+        writer.write_all(key)?;
+        for (i, state) in states.iter().enumerate() {
+            if i > 0 || !cfg.group_by.is_empty() {
+                writer.write_all(&[cfg.delimiter])?;
+            }
+            state.write(writer)?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    drop(groups);
+    drop(indices);
+    for (path, mut partition) in partitions {
+        partition.flush()?;
+        drop(partition);
+        aggregate(&mut BufReader::new(File::open(&path)?), writer, cfg, spill, level + 1)?;
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agg(data: &str, group_by: &str, aggregates: &str, max_groups: Option<usize>) -> String {
+        let mut reader = data.as_bytes();
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = Config::parse(first_line.trim_end(), b',', group_by, aggregates, false);
+        cfg.max_groups = max_groups;
+        let mut writer = Vec::new();
+        cfg.write_header(&mut writer).unwrap();
+        magg(&mut reader, &mut writer, &cfg).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let cfg = Config::parse("id,title,price", b',', "TITLE", "count(),total=sum(2),concat(id,','),min(price)", true);
+        assert_eq!(vec![1], cfg.group_by);
+        assert_eq!(vec!["title"], cfg.group_names);
+        let expected = vec![
+            Aggregate { func: Func::Count, idx: None, name: b"count()".to_vec() },
+            Aggregate { func: Func::Sum, idx: Some(2), name: b"total".to_vec() },
+            Aggregate { func: Func::Concat(b",".to_vec()), idx: Some(0), name: b"concat(id)".to_vec() },
+            Aggregate { func: Func::Min, idx: Some(2), name: b"min(price)".to_vec() },
+        ];
+        assert_eq!(expected, cfg.aggregates);
+    }
+
+    #[test]
+    #[should_panic(expected = "不明な集計関数: median")]
+    fn test_parse_unknown_func() {
+        Config::parse("id,title,price", b',', "title", "median(price)", false);
+    }
+
+    #[test]
+    #[should_panic(expected = "集計関数の指定が不正です: sum()")]
+    fn test_parse_invalid() {
+        Config::parse("id,title,price", b',', "title", "sum()", false);
+    }
+
+    #[test]
+    fn test_magg() {
+        let data = "id,name,price\n1,b,10\n2,a,9.5\n3,b,\n4,a,100\n5,b,30\n";
+        let expected = "name,count(),count(price),sum(price),avg(price),min(price),max(price)\n\
+                        b,3,2,40,20,10,30\n\
+                        a,2,2,109.5,54.75,9.5,100\n";
+        assert_eq!(expected, agg(data, "name", "count(),count(price),sum(price),avg(price),min(price),max(price)", None));
+        let expected = "name,first(id),last(price),concat(id),count_distinct(name)\n\
+                        b,1,30,1|3|5,1\n\
+                        a,2,100,2|4,1\n\
+                        c,6,x,6,1\n";
+        let data = "id,name,price\n1,b,10\n2,a,9.5\n3,b,\n4,a,100\n5,b,30\n6,c,x\n";
+        assert_eq!(expected, agg(data, "name", "first(id),last(price),concat(id,'|'),count_distinct(name)", None));
+        // 全ての行を1つのグループとして集計する。数値として解釈できない値はバイト列として比較する
+        assert_eq!("count(),count_distinct(name),min(price)\n6,3,9.5\n", agg(data, "", "count(),count_distinct(name),min(price)", None));
+        assert_eq!("count()\n0\n", agg("id\n", "", "count()", None));
+    }
+
+    #[test]
+    fn test_magg_not_number() {
+        // 空文字列とカラムが足りない行は集計しない
+        let cfg = Config::parse("name,price", b',', "name", "sum(price)", false);
+        let mut writer = Vec::new();
+        magg(&mut "c\nc,\n".as_bytes(), &mut writer, &cfg).unwrap();
+        assert_eq!("c,0\n", String::from_utf8(writer).unwrap());
+        let mut reader = "c,x\n".as_bytes();
+        let err = magg(&mut reader, &mut Vec::new(), &cfg).unwrap_err();
+        assert_eq!("sum(price) の値を数値として解釈できません: x", err.to_string());
+    }
+
+    #[test]
+    fn test_magg_spill() {
+        // グループ数の上限を超えた場合は一時ファイルに書き出して集計する
+        let mut data = String::from("id,key\n");
+        for i in 0..1000 {
+            data.push_str(&format!("{},{}\n", i, (i * 7919) % 101));
+        }
+        let parse = |output: String| -> Vec<String> {
+            let mut lines: Vec<String> = output.lines().map(String::from).collect();
+            lines.sort();
+            lines
+        };
+        let expected = parse(agg(&data, "key", "count(),sum(id),first(id),last(id)", None));
+        assert_eq!(102, expected.len());
+        for max_groups in &[1, 10, 100] {
+            assert_eq!(expected, parse(agg(&data, "key", "count(),sum(id),first(id),last(id)", Some(*max_groups))));
+        }
+    }
+
+    #[test]
+    fn test_magg_max_groups_zero() {
+        let mut cfg = Config::parse("id,key", b',', "key", "count()", false);
+        cfg.max_groups = Some(0);
+        let err = magg(&mut "1,a\n".as_bytes(), &mut Vec::new(), &cfg).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+}
@@ -0,0 +1,145 @@
+//! ヘッダの異なる複数のファイルをカラム名で揃えて連結する
+use crate::mcut;
+use crate::reader::Reader;
+use crate::util;
+use std::io;
+use std::io::prelude::*;
+
+/// 出力するカラムの決め方
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    /// いずれかのファイルに存在するカラムを全て出力する
+    Union,
+    /// 全てのファイルに存在するカラムのみ出力する
+    Intersection,
+}
+
+/// 連結の設定
+pub struct Config {
+    pub delimiter: u8,
+    /// 出力するカラム名
+    pub columns: Vec<String>,
+    /// trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub ignore_case: bool,
+    /// ファイルに存在しないカラムに出力する値
+    pub default: Vec<u8>,
+    /// 入力ファイル名を出力するカラムのカラム名。Noneの場合は出力しない
+    pub source_column: Option<String>,
+    /// この文字列で始まる行はコメント行とみなし、出力しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// 全てのファイルのヘッダ行から出力するカラムを決めてConfigを生成する
+    ///
+    /// 出力するカラムの順番は、カラム名が最初に現れたファイルでの順番とする。
+    /// 同じファイルに同じカラム名が複数ある場合は先頭のカラムのみ出力する。
+    ///
+    /// # Arguments
+    /// * `first_lines` - 全てのファイルのヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `mode`        - 出力するカラムの決め方
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn new(first_lines: &[&str], delimiter: u8, mode: Mode, ignore_case: bool) -> Self {
+        let mut columns: Vec<String> = Vec::new();
+        for first_line in first_lines {
+            for name in mcut::Config::split_header(first_line, delimiter) {
+                if find(&columns, name, ignore_case).is_none() {
+                    columns.push(name.to_string());
+                }
+            }
+        }
+        if mode == Mode::Intersection {
+            columns.retain(|name| {
+                first_lines.iter().all(|first_line| find(&mcut::Config::split_header(first_line, delimiter), name, ignore_case).is_some())
+            });
+        }
+        Config { delimiter, columns, ignore_case, default: Vec::new(), source_column: None, comment_prefix: None }
+    }
+
+    /// 出力するカラム名をヘッダとして出力する
+    ///
+    /// # Arguments
+    /// * `writer` - ヘッダ行を書き込むwriter
+    pub fn write_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut names: Vec<&str> = self.columns.iter().map(|name| name.as_str()).collect();
+        if let Some(ref source_column) = self.source_column {
+            names.push(source_column);
+        }
+        writeln!(writer, "{}", util::join(self.delimiter as char, &names))
+    }
+}
+
+/// カラム名の一覧から name と一致するカラムの位置を返す
+fn find<S: AsRef<str>>(names: &[S], name: &str, ignore_case: bool) -> Option<usize> {
+    if ignore_case {
+        let normalized = mcut::Config::normalize_name(name);
+        names.iter().position(|e| mcut::Config::normalize_name(e.as_ref()) == normalized)
+    } else {
+        names.iter().position(|e| e.as_ref() == name)
+    }
+}
+
+/// 1つのファイルの行を出力するカラムに揃えてwriterに書き込む
+///
+/// # Arguments
+/// * `reader`     - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `first_line` - このファイルのヘッダ行
+/// * `source`     - source_column に出力するファイル名
+/// * `cfg`        - 出力するカラムの設定
+pub fn mcat<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, first_line: &str, source: &str, cfg: &Config) -> io::Result<()> {
+    let header = mcut::Config::split_header(first_line, cfg.delimiter);
+    let mapping: Vec<Option<usize>> = cfg.columns.iter().map(|name| find(&header, name, cfg.ignore_case)).collect();
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        for (i, idx) in mapping.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(&[cfg.delimiter])?;
+            }
+            match idx {
+                // カラムが足りない行は空文字列とみなす
+                Some(idx) => writer.write_all(record.field(*idx))?,
+                None      => writer.write_all(&cfg.default)?,
+            }
+        }
+        if cfg.source_column.is_some() {
+            if !mapping.is_empty() {
+                writer.write_all(&[cfg.delimiter])?;
+            }
+            writer.write_all(source.as_bytes())?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADERS: &[&str] = &["id,title,price", "ID,price,category", "price,id,title"];
+
+    #[test]
+    fn test_new() {
+        assert_eq!(vec!["id", "title", "price", "ID", "category"], Config::new(HEADERS, b',', Mode::Union, false).columns);
+        assert_eq!(vec!["id", "title", "price", "category"], Config::new(HEADERS, b',', Mode::Union, true).columns);
+        assert_eq!(vec!["price"], Config::new(HEADERS, b',', Mode::Intersection, false).columns);
+        assert_eq!(vec!["id", "price"], Config::new(HEADERS, b',', Mode::Intersection, true).columns);
+    }
+
+    #[test]
+    fn test_mcat() {
+        let mut cfg = Config::new(HEADERS, b',', Mode::Union, true);
+        cfg.default = b"-".to_vec();
+        cfg.source_column = Some(String::from("src"));
+        let mut writer = Vec::new();
+        cfg.write_header(&mut writer).unwrap();
+        mcat(&mut "1,a,100\n2,b\n".as_bytes(), &mut writer, HEADERS[0], "a.csv", &cfg).unwrap();
+        mcat(&mut "3,200,x\n".as_bytes(), &mut writer, HEADERS[1], "b.csv", &cfg).unwrap();
+        mcat(&mut "300,4,c\n".as_bytes(), &mut writer, HEADERS[2], "c.csv", &cfg).unwrap();
+        let expected = "id,title,price,category,src\n1,a,100,-,a.csv\n2,b,,-,a.csv\n3,-,200,x,b.csv\n4,c,300,-,c.csv\n";
+        assert_eq!(expected, String::from_utf8(writer).unwrap());
+    }
+}
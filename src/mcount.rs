@@ -0,0 +1,250 @@
+//! ヘッダのカラム名で指定したカラムの値ごとの出現回数を数える
+use crate::mcut;
+use crate::reader::{Reader, Record};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::io::prelude::*;
+
+/// 出現回数の集計の設定
+pub struct Config {
+    pub delimiter: u8,
+    /// 値を数えるカラムの0から始まるカラム番号
+    pub columns: Vec<usize>,
+    /// 値を数えるカラムのカラム名
+    pub names: Vec<String>,
+    /// 出現回数の多い順に出力する件数の上限
+    pub top: Option<usize>,
+    /// メモリ上で数える値の種類の上限(1以上)。超えた場合は Space-Saving アルゴリズムで出現回数の多い値を近似的に求める
+    pub capacity: Option<usize>,
+    /// この文字列で始まる行はコメント行とみなし、数えない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// ヘッダ行と値を数えるカラムの指定からConfigを生成する
+    ///
+    /// # Arguments
+    /// * `first_line`  - ヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `fields`      - 値を数えるカラム名のカンマ区切り。複数指定した場合は値の組み合わせごとに数える
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn parse_fields(first_line: &str, delimiter: u8, fields: &str, ignore_case: bool) -> Self {
+        let header = mcut::Config::split_header(first_line, delimiter);
+        let columns: Vec<usize> = fields.split(',').map(|col_name| {
+            mcut::Config::find_column(col_name, &header, ignore_case).unwrap_or_else(|| {
+                panic!("不明なフィールド: {}{}", col_name, mcut::Config::unknown_field_hint(col_name, &header));
+            })
+        }).collect();
+        let names = columns.iter().map(|idx| header[*idx].to_string()).collect();
+        Config { delimiter, columns, names, top: None, capacity: None, comment_prefix: None }
+    }
+
+    /// 値を数えるカラムのカラム名と「count」「percent」をヘッダとして出力する
+    ///
+    /// # Arguments
+    /// * `writer` - ヘッダ行を書き込むwriter
+    pub fn write_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut names: Vec<&[u8]> = self.names.iter().map(|name| name.as_bytes()).collect();
+        names.push(b"count");
+        names.push(b"percent");
+        writer.write_all(&names.join(&self.delimiter))?;
+        writer.write_all(b"\n")
+    }
+
+    /// 値を数えるカラムの値を区切り文字で連結したキーを返す。カラムが足りない行は空文字列とみなす
+    fn key(&self, record: &Record) -> Vec<u8> {
+        let values: Vec<&[u8]> = self.columns.iter().map(|idx| record.field(*idx)).collect();
+        values.join(&self.delimiter)
+    }
+}
+
+/// 値ごとの出現回数
+enum Counter {
+    /// 全ての値の出現回数を正確に数える
+    Exact(HashMap<Vec<u8>, u64>),
+    SpaceSaving(SpaceSaving),
+}
+
+impl Counter {
+    fn add(&mut self, key: Vec<u8>) {
+        match self {
+            Counter::Exact(counts)          => *counts.entry(key).or_insert(0) += 1,
+            Counter::SpaceSaving(counter)   => counter.add(key),
+        }
+    }
+
+    /// (値, 出現回数) の一覧と、出現回数が近似値かどうかを返す
+    fn into_counts(self) -> (Vec<(Vec<u8>, u64)>, bool) {
+        match self {
+            Counter::Exact(counts)          => (counts.into_iter().collect(), false),
+            Counter::SpaceSaving(counter)   => (counter.slots, counter.evicted),
+        }
+    }
+}
+
+/// Space-Saving アルゴリズムで出現回数の多い値を数える
+///
+/// 値の種類が capacity を超えた場合は、出現回数が最小の値を新しい値で置き換え、最小の出現回数+1から数え始める。
+/// 出現回数は実際の値以上となり、その差は置き換えた時点の最小の出現回数以下となる。
+pub(crate) struct SpaceSaving {
+    capacity: usize,
+    /// (値, 出現回数)
+    slots: Vec<(Vec<u8>, u64)>,
+    /// 値からslotsの添字への索引
+    indices: HashMap<Vec<u8>, usize>,
+    /// 出現回数が最小の値を探すための (出現回数, slotsの添字) の集合
+    order: BTreeSet<(u64, usize)>,
+    /// 値を置き換えたかどうか
+    evicted: bool,
+}
+
+impl SpaceSaving {
+    /// # Arguments
+    /// * `capacity` - メモリ上で数える値の種類の上限。1以上の値を指定する
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "SpaceSaving の capacity には1以上の値を指定してください");
+        SpaceSaving { capacity, slots: Vec::new(), indices: HashMap::new(), order: BTreeSet::new(), evicted: false }
+    }
+
+    /// 値の出現回数を1増やす
+    pub fn add(&mut self, key: Vec<u8>) {
+        let i = match self.indices.get(&key) {
+            Some(i) => *i,
+            None if self.slots.len() < self.capacity => {
+                self.indices.insert(key.clone(), self.slots.len());
+                self.slots.push((key, 0));
+                self.order.insert((0, self.slots.len() - 1));
+                self.slots.len() - 1
+            }
+            None => {
+                let (_, i) = *self.order.iter().next().unwrap();
+                self.indices.remove(&self.slots[i].0);
+                self.indices.insert(key.clone(), i);
+                self.slots[i].0 = key;
+                self.evicted = true;
+                i
+            }
+        };
+        let count = self.slots[i].1;
+        self.order.remove(&(count, i));
+        self.order.insert((count + 1, i));
+        self.slots[i].1 = count + 1;
+    }
+
+    /// 出現回数の多い順に最大n件の (値, 出現回数) を返す。出現回数が等しい場合は値のバイト列の昇順とする
+    pub fn top(&self, n: usize) -> Vec<(&[u8], u64)> {
+        let mut counts: Vec<(&[u8], u64)> = self.slots.iter().map(|(key, count)| (&key[..], *count)).collect();
+        counts.sort_by(|(a, count_a), (b, count_b)| count_b.cmp(count_a).then_with(|| a.cmp(b)));
+        counts.truncate(n);
+        counts
+    }
+}
+
+/// readerから読み込んだ行のcfgのカラムの値ごとの出現回数と割合(%)を、出現回数の多い順にwriterに書き込む
+///
+/// 出現回数が等しい場合は値のバイト列の昇順に出力する。
+/// 出現回数が近似値の場合(値の種類が cfg.capacity を超えた場合)はtrueを返す。
+/// cfg.capacity が Some(0) の場合は io::ErrorKind::InvalidInput のエラーを返す。
+///
+/// # Arguments
+/// * `reader` - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `cfg`    - 値を数えるカラムや出力する件数の設定
+pub fn mcount<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, cfg: &Config) -> io::Result<bool> {
+    if cfg.capacity == Some(0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "capacity には1以上の値を指定してください"));
+    }
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let mut counter = match cfg.capacity {
+        Some(capacity) => Counter::SpaceSaving(SpaceSaving::new(capacity)),
+        None => Counter::Exact(HashMap::new()),
+    };
+    let mut total: u64 = 0;
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        counter.add(cfg.key(&record));
+        total += 1;
+    }
+    let (mut counts, approximate) = counter.into_counts();
+    counts.sort_by(|(a, count_a), (b, count_b)| count_b.cmp(count_a).then_with(|| a.cmp(b)));
+    if let Some(top) = cfg.top {
+        counts.truncate(top);
+    }
+    for (key, count) in &counts {
+        writer.write_all(key)?;
+        writeln!(writer, "{}{}{}{:.2}", cfg.delimiter as char, count, cfg.delimiter as char, *count as f64 * 100.0 / total as f64)?;
+    }
+    Ok(approximate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(data: &str, fields: &str, top: Option<usize>, capacity: Option<usize>) -> (String, bool) {
+        let mut reader = data.as_bytes();
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = Config::parse_fields(first_line.trim_end(), b',', fields, false);
+        cfg.top = top;
+        cfg.capacity = capacity;
+        let mut writer = Vec::new();
+        cfg.write_header(&mut writer).unwrap();
+        let approximate = mcount(&mut reader, &mut writer, &cfg).unwrap();
+        (String::from_utf8(writer).unwrap(), approximate)
+    }
+
+    #[test]
+    fn test_mcount() {
+        let data = "id,name,kind\n1,b,x\n2,a,y\n3,b,x\n4,c\n5,b,y\n6,a,y\n7,c,\n";
+        let expected = "name,count,percent\nb,3,42.86\na,2,28.57\nc,2,28.57\n";
+        assert_eq!((expected.to_string(), false), count(data, "name", None, None));
+        assert_eq!(("name,count,percent\nb,3,42.86\n".to_string(), false), count(data, "name", Some(1), None));
+        // 値の組み合わせごとに数える。カラムが足りない行は空文字列とみなす
+        let expected = "name,kind,count,percent\na,y,2,28.57\nb,x,2,28.57\nc,,2,28.57\n";
+        assert_eq!((expected.to_string(), false), count(data, "name,kind", Some(3), None));
+        assert_eq!(("kind,count,percent\n".to_string(), false), count("kind\n", "kind", None, None));
+    }
+
+    #[test]
+    #[should_panic(expected = "不明なフィールド: nam")]
+    fn test_parse_fields_unknown() {
+        Config::parse_fields("id,name", b',', "nam", false);
+    }
+
+    #[test]
+    fn test_mcount_space_saving() {
+        // 値の種類が上限以下なら正確に数える
+        let data = "id,name,kind\n1,b,x\n2,a,y\n3,b,x\n4,c\n5,b,y\n6,a,y\n7,c,\n";
+        assert_eq!(count(data, "name", None, None).0, count(data, "name", None, Some(3)).0);
+        // 出現回数の多い値は上限を超えても残る
+        let mut data = String::from("id,key\n");
+        for i in 0..10000 {
+            let key = if i % 3 == 0 { "hot".to_string() } else if i % 7 == 0 { "warm".to_string() } else { format!("cold{}", i) };
+            data.push_str(&format!("{},{}\n", i, key));
+        }
+        let (output, approximate) = count(&data, "key", Some(2), Some(100));
+        assert!(approximate);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[1].starts_with("hot,"));
+        assert!(lines[2].starts_with("warm,"));
+        // 出現回数は実際の値以上になる
+        let hot: u64 = lines[1].split(',').nth(1).unwrap().parse().unwrap();
+        assert!(hot >= 3334);
+    }
+
+    #[test]
+    fn test_mcount_capacity_zero() {
+        let mut cfg = Config::parse_fields("id,name", b',', "name", false);
+        cfg.capacity = Some(0);
+        let err = mcount(&mut "1,a\n".as_bytes(), &mut Vec::new(), &cfg).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    #[should_panic(expected = "SpaceSaving の capacity には1以上の値を指定してください")]
+    fn test_space_saving_capacity_zero() {
+        SpaceSaving::new(0);
+    }
+}
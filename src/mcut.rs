@@ -1,5 +1,10 @@
 extern crate memchr;
+extern crate regex;
 
+use crate::reader::Reader;
+use crate::util;
+use regex::Regex;
+use std::collections::VecDeque;
 use std::io::prelude::*;
 use std::io::BufReader;
 
@@ -10,57 +15,283 @@ use std::io::BufReader;
 /// * `writer`
 /// * `cfg`    - 区切り文字や出力対象カラム番号を格納したオブジェクト
 pub fn mcut<R: Read, W: Write>(reader: &mut BufReader<R>, writer: &mut W, cfg: Config) {
-    let last_col = cfg.columns.len() - 1;
     let col_len: usize = cfg.columns.iter().map(|column| column.idx).max().unwrap() + 1;
     // 読み込んだ文字列を格納する配列
     let mut buf: Vec<u8> = Vec::new();
     // 区切り文字のindexを格納する配列
     let mut split: Vec<usize> = vec![0; col_len + 1];
-    while reader.read_until(b'\n', &mut buf).ok().unwrap() > 0 {
-        // 改行を区切り文字に置換
-        let buf_last = buf.len() - 1;
-        if buf[buf_last] == b'\n' {
-            buf[buf_last] = cfg.delimiter;
-        } else {
-            buf.push(cfg.delimiter);
+    // 仮想カラムに出力する行番号とファイル先頭からのバイト数
+    let mut line_no = cfg.line_no;
+    let mut offset = cfg.offset;
+    // 読み込んだ行数
+    let mut row_count: usize = 0;
+    // 出力対象とした行数
+    let mut output_count: usize = 0;
+    // --tail 指定時に末尾の行を保持するリングバッファ
+    let mut tail: VecDeque<Vec<u8>> = VecDeque::new();
+    // --sample-rows 指定時に(出力対象とした順番, 行)を保持するリザーバ
+    let mut reservoir: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut rng = util::Rng::new(cfg.seed);
+    while cfg.limit.map_or(true, |limit| output_count < limit) && reader.read_until(b'\n', &mut buf).ok().unwrap() > 0 {
+        let read_len = buf.len();
+        if cfg.comment_prefix.as_ref().map_or(false, |prefix| buf.starts_with(prefix)) {
+            // コメント行は行数に数えない
+            if cfg.pass_comments {
+                writer.write_all(&buf).unwrap();
+                if buf.last() != Some(&b'\n') {
+                    writer.write_all(b"\n").unwrap();
+                }
+            }
+            buf.clear();
+            line_no += 1;
+            offset += read_len as u64;
+            continue;
         }
-
-        // 必要なところまで読み込む
-        for (i, position) in memchr::memchr_iter(cfg.delimiter, &buf).enumerate() {
-            if i < col_len {
-                // 区切り文字も含んだindex
-                split[i + 1] = position + 1;
+        let target = if row_count >= cfg.skip {
+            let target = match cfg.sample {
+                None => Target::Write,
+                Some(Sample::Fraction(fraction)) => {
+                    if rng.next_f64() < fraction { Target::Write } else { Target::Discard }
+                }
+                Some(Sample::Rows(n)) => {
+                    let slot = if output_count < n { output_count } else { rng.below(output_count + 1) };
+                    if slot < n { Target::Reservoir(slot) } else { Target::Discard }
+                }
+            };
+            output_count += 1;
+            target
+        } else {
+            Target::Discard
+        };
+        if target != Target::Discard {
+            // 改行を区切り文字に置換
+            let buf_last = buf.len() - 1;
+            if buf[buf_last] == b'\n' {
+                buf[buf_last] = cfg.delimiter;
             } else {
-                break;
+                buf.push(cfg.delimiter);
             }
-        }
+            let row = Row { filename: &cfg.filename, line_no, offset, line: &buf[..buf.len() - 1] };
 
-        // 書き込み処理
-        for column in (&cfg.columns)[0..last_col].iter() {
-            match column {
-                &Column { idx: _, default: Some(ref default), name: _} => {
-                    writer.write(default).unwrap();
-                    writer.write(&[cfg.delimiter]).unwrap();
-                },
-                &Column { idx, default: None, name: _} => {
-                    let start = split[idx];
-                    let end   = split[idx + 1];
-                    writer.write(&buf[start..end]).unwrap();
+            // 必要なところまで読み込む
+            for (i, position) in memchr::memchr_iter(cfg.delimiter, &buf).enumerate() {
+                if i < col_len {
+                    // 区切り文字も含んだindex
+                    split[i + 1] = position + 1;
+                } else {
+                    break;
                 }
             }
+
+            // 書き込み処理
+            if let Target::Reservoir(slot) = target {
+                if slot < reservoir.len() {
+                    reservoir[slot].0 = output_count;
+                    reservoir[slot].1.clear();
+                } else {
+                    reservoir.push((output_count, Vec::new()));
+                }
+                write_row(&mut reservoir[slot].1, &cfg, &buf, &split, &row);
+            } else if let Some(n) = cfg.tail {
+                let mut out = if tail.len() >= n { tail.pop_front().unwrap_or_default() } else { Vec::new() };
+                out.clear();
+                write_row(&mut out, &cfg, &buf, &split, &row);
+                if n > 0 {
+                    tail.push_back(out);
+                }
+            } else {
+                write_row(writer, &cfg, &buf, &split, &row);
+            }
         }
-        match cfg.columns[last_col] {
-            Column { idx: _, default: Some(ref default), name: _} => {
-                writer.write(default).unwrap();
+        buf.clear();
+        row_count += 1;
+        line_no += 1;
+        offset += read_len as u64;
+    }
+    // リザーバの行は元の順番で出力する
+    reservoir.sort_by_key(|(i, _)| *i);
+    let start = cfg.tail.map_or(0, |n| reservoir.len().saturating_sub(n));
+    for (_, out) in reservoir[start..].iter() {
+        writer.write_all(out).unwrap();
+    }
+    for out in tail.iter() {
+        writer.write_all(out).unwrap();
+    }
+}
+
+/// 読み込んだ行の出力先
+#[derive(PartialEq)]
+enum Target {
+    /// そのまま出力する(--tail 指定時はリングバッファに格納する)
+    Write,
+    /// リザーバの指定した位置に格納する
+    Reservoir(usize),
+    /// 出力しない
+    Discard,
+}
+
+/// 行のサンプリング方法
+#[derive(PartialEq,Debug,Clone,Copy)]
+pub enum Sample {
+    /// リザーバサンプリングで指定した行数を抽出する
+    Rows(usize),
+    /// 各行を指定した確率で抽出する
+    Fraction(f64),
+}
+
+/// 区切り文字で分割済みの1行をcfgの設定に従って書き込む
+///
+/// # Arguments
+/// * `writer`
+/// * `cfg`    - 区切り文字や出力対象カラム番号を格納したオブジェクト
+/// * `buf`    - 改行を区切り文字に置換した1行
+/// * `split`  - bufの区切り文字のindex
+/// * `row`    - 仮想カラムに出力する行の情報
+fn write_row<W: Write>(writer: &mut W, cfg: &Config, buf: &[u8], split: &[usize], row: &Row) {
+    let last_col = cfg.columns.len() - 1;
+    for column in (&cfg.columns)[0..last_col].iter() {
+        match *column {
+            Column { virtual_column: Some(ref virtual_column), .. } => {
+                virtual_column.write(writer, row);
+                writer.write_all(&[cfg.delimiter]).unwrap();
             },
-            Column { idx, default: None, name: _} => {
+            Column { default: Some(ref default), .. } => {
+                writer.write_all(default).unwrap();
+                writer.write_all(&[cfg.delimiter]).unwrap();
+            },
+            Column { idx, default: None, .. } => {
                 let start = split[idx];
-                let end   = split[idx + 1] - 1;
-                writer.write(&buf[start..end]).unwrap();
+                let end   = split[idx + 1];
+                writer.write_all(&buf[start..end]).unwrap();
             }
         }
-        writer.write(b"\n").unwrap();
+    }
+    match cfg.columns[last_col] {
+        Column { virtual_column: Some(ref virtual_column), .. } => {
+            virtual_column.write(writer, row);
+        },
+        Column { default: Some(ref default), .. } => {
+            writer.write_all(default).unwrap();
+        },
+        Column { idx, default: None, .. } => {
+            let start = split[idx];
+            let end   = split[idx + 1] - 1;
+            writer.write_all(&buf[start..end]).unwrap();
+        }
+    }
+    writer.write_all(b"\n").unwrap();
+}
+
+/// ヘッダ行より前にある行の扱い
+pub struct Preamble {
+    /// ヘッダを探す前に読み飛ばす行数
+    pub skip_lines: usize,
+    /// この文字列で始まる行はヘッダとみなさない
+    pub comment_prefix: Option<Vec<u8>>,
+    /// この正規表現にマッチする最初の行をヘッダとみなす
+    pub header_pattern: Option<Regex>,
+}
+
+/// read_headerで読み込んだヘッダ行
+pub struct Header {
+    /// 改行を除いたヘッダ行
+    pub line: String,
+    /// ヘッダより前に読み飛ばした行数
+    pub skipped_lines: usize,
+    /// ヘッダより前に読み飛ばしたバイト数
+    pub skipped_bytes: u64,
+    /// 改行文字を含めたヘッダ行のバイト数
+    pub header_bytes: u64,
+}
+
+impl Header {
+    /// 読み飛ばした行とヘッダ行の実際のバイト数をcfgの仮想カラムの行番号とバイト数に反映する
+    ///
+    /// # Arguments
+    /// * `cfg` - ヘッダ行から生成したConfig
+    pub fn apply(&self, cfg: &mut Config) {
+        cfg.line_no += self.skipped_lines;
+        cfg.header_offset = self.skipped_bytes;
+        cfg.offset = self.skipped_bytes + self.header_bytes;
+    }
+}
+
+/// preambleの設定に従ってヘッダより前の行を読み飛ばし、ヘッダ行を読み込む
+///
+/// # Arguments
+/// * `reader`
+/// * `preamble` - ヘッダ行より前にある行の扱い
+pub fn read_header<R: BufRead>(reader: &mut R, preamble: &Preamble) -> Option<Header> {
+    let mut buf: Vec<u8> = Vec::new();
+    let mut skipped_lines = 0;
+    let mut skipped_bytes = 0;
+    loop {
         buf.clear();
+        if reader.read_until(b'\n', &mut buf).ok()? == 0 {
+            return None;
+        }
+        let read_len = buf.len() as u64;
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
+            }
+        }
+        let is_comment = preamble.comment_prefix.as_ref().map_or(false, |prefix| buf.starts_with(prefix));
+        let line = String::from_utf8_lossy(&buf);
+        let is_header = skipped_lines >= preamble.skip_lines
+            && !is_comment
+            && preamble.header_pattern.as_ref().map_or(true, |pattern| pattern.is_match(&line));
+        if is_header {
+            return Some(Header { line: line.into_owned(), skipped_lines, skipped_bytes, header_bytes: read_len });
+        }
+        skipped_lines += 1;
+        skipped_bytes += read_len;
+    }
+}
+
+/// ヘッダのカラム一覧を先頭sample行分の値とともに出力し、カラム数がヘッダと異なる行を報告する
+///
+/// # Arguments
+/// * `reader`
+/// * `writer`
+/// * `header`         - read_headerで読み込んだヘッダ行。報告する行番号はヘッダより前に読み飛ばした行も数える
+/// * `delimiter`      - 区切り文字
+/// * `comment_prefix` - この文字列で始まる行はコメント行とみなし、値の出力とカラム数の確認から除く
+/// * `sample`         - 値を出力する行数
+pub fn list_columns<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, header: &Header, delimiter: u8, comment_prefix: Option<&[u8]>, sample: usize) {
+    // ヘッダ行の行番号(1始まり)
+    let header_line_no = header.skipped_lines + 1;
+    let header = Config::split_header(&header.line, delimiter);
+    let mut samples: Vec<Vec<String>> = vec![Vec::new(); header.len()];
+    // カラム数がヘッダと異なる行の(行番号, カラム数)
+    let mut ragged: Vec<(usize, usize)> = Vec::new();
+    let mut reader = Reader::new(reader, delimiter).with_comment_prefix(comment_prefix);
+    let mut row_count = 0;
+    while let Some(record) = reader.read_record().unwrap() {
+        row_count += 1;
+        if row_count <= sample {
+            for (i, value) in record.iter().take(header.len()).enumerate() {
+                samples[i].push(String::from_utf8_lossy(value).into_owned());
+            }
+        }
+        let len = record.len();
+        if len != header.len() {
+            ragged.push((header_line_no + reader.line_no(), len));
+        }
+    }
+
+    writeln!(writer, "index\tname\tsamples").unwrap();
+    for (i, name) in header.iter().enumerate() {
+        writeln!(writer, "{}\t{}\t{}", i, name, util::join('\t', &samples[i])).unwrap();
+    }
+    if !ragged.is_empty() {
+        writeln!(writer).unwrap();
+        writeln!(writer, "カラム数がヘッダ({})と異なる行: {}行", header.len(), ragged.len()).unwrap();
+        writeln!(writer, "line\tcolumns").unwrap();
+        for (line_no, len) in ragged.iter() {
+            writeln!(writer, "{}\t{}", line_no, len).unwrap();
+        }
     }
 }
 
@@ -69,33 +300,186 @@ pub struct Config {
     pub delimiter: u8,
     pub field: String,
     pub columns: Vec<Column>,
+    /// @filename に出力するファイル名
+    pub filename: String,
+    /// mcutが最初に読み込む行の行番号(1始まり)
+    pub line_no: usize,
+    /// mcutが最初に読み込む行のファイル先頭からのバイト数
+    pub offset: u64,
+    /// ヘッダ行のファイル先頭からのバイト数
+    pub header_offset: u64,
+    /// 読み飛ばす先頭の行数
+    pub skip: usize,
+    /// 出力する最大の行数
+    pub limit: Option<usize>,
+    /// 末尾から出力する行数
+    pub tail: Option<usize>,
+    /// 行のサンプリング方法(--skip, --limit の後, --tail の前に適用される)
+    pub sample: Option<Sample>,
+    /// サンプリングに利用する乱数のシード
+    pub seed: u64,
+    /// この文字列で始まる行をコメント行とみなす
+    pub comment_prefix: Option<Vec<u8>>,
+    /// trueの場合はコメント行をそのまま出力し、falseの場合は出力しない。
+    /// コメント行は読み込んだ時点で出力するため、tail や Sample::Rows で保持した行より先に出力される
+    pub pass_comments: bool,
 }
 
 impl Config {
     fn new(first_line: String, delimiter: u8, field: String, columns: Vec<Column>) -> Config {
-        Config { first_line, delimiter, field, columns }
+        // 改行を「\n」とみなした値。read_header で読み込んだ場合は Header::apply で実際のバイト数に置き換える
+        let offset = first_line.len() as u64 + 1;
+        Config {
+            first_line, delimiter, field, columns,
+            filename: String::new(), line_no: 2, offset, header_offset: 0,
+            skip: 0, limit: None, tail: None,
+            sample: None, seed: util::Rng::seed_from_time(),
+            comment_prefix: None, pass_comments: false,
+        }
+    }
+
+    /// Configをヘッダと出力対象カラムから直接生成するbuilderを返す
+    ///
+    /// ```
+    /// use mtools::mcut::Config;
+    ///
+    /// let cfg = Config::builder()
+    ///     .delimiter(b',')
+    ///     .header(&["id", "title", "kana", "narrow1"])
+    ///     .column("title")
+    ///     .constant("src", "0")
+    ///     .range("kana", "narrow1")
+    ///     .build();
+    /// assert_eq!("title,src:0,kana..narrow1", cfg.field);
+    /// ```
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder { delimiter: b'\t', header: None, fields: Vec::new(), ignore_case: false }
     }
 
-    fn col_to_idx(col_name: &str, header: &[&str], is_start: bool) -> usize {
+    /// ヘッダ行をカラム名の配列に分割する
+    ///
+    /// # Arguments
+    /// * `first_line` - ファイルの1行目のヘッダ文字列
+    /// * `delimiter`  - 区切り文字
+    pub fn split_header(first_line: &str, delimiter: u8) -> Vec<&str> {
+        first_line.split(char::from(delimiter)).collect()
+    }
+
+    /// カラム名もしくはカラム番号をindexに変換する
+    ///
+    /// # Arguments
+    /// * `col_name`    - カラム名もしくは0から始まるカラム番号
+    /// * `header`      - ヘッダのカラム名の配列
+    /// * `is_start`    - 範囲指定の開始位置であればtrue
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    fn col_to_idx(col_name: &str, header: &[&str], is_start: bool, ignore_case: bool) -> usize {
         if col_name.is_empty() {
             return if is_start { 0 } else { header.len() };
         }
-        if let Some(idx) = col_name.trim().parse::<usize>().ok() { // カラム番号が指定されている場合
+        if let Some(idx) = Self::find_column(col_name, header, ignore_case) {
+            return if is_start { idx } else { idx + 1};
+        }
+        panic!("不明なフィールド: {}{}", col_name, Self::unknown_field_hint(col_name, header));
+    }
+
+    /// カラム名もしくはカラム番号に対応するカラムのindexを返す
+    ///
+    /// # Arguments
+    /// * `col_name`    - カラム名、「カラム名#n」もしくは0から始まるカラム番号
+    /// * `header`      - ヘッダのカラム名の配列
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn find_column(col_name: &str, header: &[&str], ignore_case: bool) -> Option<usize> {
+        if let Ok(idx) = col_name.trim().parse::<usize>() { // カラム番号が指定されている場合
             if idx < header.len() {
-                return if is_start { idx } else { idx + 1};
+                return Some(idx);
             }
         }
         if let Some(idx) = header.iter().position(|e| e == &col_name) {
-            return if is_start { idx } else { idx + 1};
+            return Some(idx);
+        }
+        if let Some(idx) = Self::qualified_col_to_idx(col_name, header, false) { // 「カラム名#n」で指定されている場合
+            return Some(idx);
+        }
+        if ignore_case {
+            let normalized = Self::normalize_name(col_name);
+            if let Some(idx) = header.iter().position(|e| Self::normalize_name(e) == normalized) {
+                return Some(idx);
+            }
+            return Self::qualified_col_to_idx(col_name, header, true);
+        }
+        None
+    }
+
+    /// 大文字小文字と前後の空白を無視して比較するためにカラム名を正規化する
+    pub(crate) fn normalize_name(name: &str) -> String {
+        name.trim().to_lowercase()
+    }
+
+    /// 不明なフィールドが指定された場合に、編集距離の近いカラム名と利用可能なカラムの一覧を返す
+    ///
+    /// # Arguments
+    /// * `col_name` - 見つからなかったカラム指定
+    /// * `header`   - ヘッダのカラム名の配列
+    pub(crate) fn unknown_field_hint(col_name: &str, header: &[&str]) -> String {
+        let normalized = Self::normalize_name(col_name);
+        let threshold = std::cmp::max(2, normalized.chars().count() / 3);
+        let mut candidates: Vec<(usize, &str)> = header.iter()
+            .map(|e| (util::edit_distance(&normalized, &Self::normalize_name(e)), *e))
+            .filter(|(distance, _)| *distance <= threshold)
+            .collect();
+        candidates.sort_by_key(|(distance, _)| *distance);
+        candidates.dedup_by_key(|(_, name)| *name);
+        let mut hint = String::new();
+        if !candidates.is_empty() {
+            let names: Vec<&str> = candidates.iter().take(3).map(|(_, name)| *name).collect();
+            hint.push_str(&format!("\n  もしかして: {}", util::join(',', &names)));
+        }
+        let available: Vec<String> = header.iter().enumerate().map(|(i, e)| format!("{}:{}", i, e)).collect();
+        hint.push_str(&format!("\n  利用可能なカラム: {}", util::join(',', &available)));
+        hint
+    }
+
+    /// 「カラム名#n」形式の指定をn番目(1始まり)に出現するカラムのindexに変換する
+    ///
+    /// # Arguments
+    /// * `col_name`    - 「カラム名#n」形式のカラム指定
+    /// * `header`      - ヘッダのカラム名の配列
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    fn qualified_col_to_idx(col_name: &str, header: &[&str], ignore_case: bool) -> Option<usize> {
+        let pos = col_name.rfind('#')?;
+        let (name, nth) = (&col_name[..pos], &col_name[pos + 1..]);
+        let nth = nth.parse::<usize>().ok()?;
+        if nth == 0 {
+            return None;
+        }
+        let normalized = Self::normalize_name(name);
+        header.iter().enumerate()
+            .filter(|(_, e)| if ignore_case { Self::normalize_name(e) == normalized } else { *e == &name })
+            .nth(nth - 1)
+            .map(|(idx, _)| idx)
+    }
+
+    /// ヘッダに重複したカラム名が存在する場合に警告を出力する
+    ///
+    /// # Arguments
+    /// * `header` - ヘッダのカラム名の配列
+    fn warn_duplicate_names(header: &[&str]) {
+        for (i, name) in header.iter().enumerate() {
+            if header[..i].contains(name) {
+                continue;
+            }
+            let count = header[i..].iter().filter(|e| *e == name).count();
+            if count > 1 {
+                eprintln!("警告: カラム名が重複しています: {} ({}個) 2つ目以降は {}#2 のように指定してください。", name, count, name);
+            }
         }
-        panic!("不明なフィールド: {}", col_name);
     }
 
     fn number_to_idx(col_name: &str, header: &[&str], is_start: bool) -> usize {
         if col_name.is_empty() {
             return if is_start { 0 } else { header.len() };
         }
-        if let Some(idx) = col_name.trim().parse::<usize>().ok() {
+        if let Ok(idx) = col_name.trim().parse::<usize>() {
             if idx < header.len() {
                 return if is_start { idx } else { idx + 1 };
             }
@@ -106,22 +490,22 @@ impl Config {
     fn parse_field(field: &str) -> (Option<&str>, Option<&str>, Option<Vec<u8>>) {
         let v1: Vec<&str> = field.splitn(2, ':').collect();
         let s1: &[&str] = &v1;
-        match s1 {
-            &[col] => {
+        match *s1 {
+            [col] => {
                 let v2: Vec<&str> = col.splitn(2, "..").collect();
                 let s2: &[&str] = &v2;
-                match s2 {
-                    &[start]      => return (Some(start), None, None),
-                    &[start, end] => return (Some(start), Some(end), None),
+                match *s2 {
+                    [start]       => (Some(start), None, None),
+                    [start, end]  => (Some(start), Some(end), None),
                     _             => panic!("不明なフィールド: {}", field),
                 }
             }
-            &[col, default] => {
+            [col, default] => {
                 let v2: Vec<&str> = col.splitn(2, "..").collect();
                 let s2: &[&str] = &v2;
-                match s2 {
-                    &[start]      => return (Some(start), None, Some(default.as_bytes().to_vec())),
-                    &[start, end] => return (Some(start), Some(end), Some(default.as_bytes().to_vec())),
+                match *s2 {
+                    [start]       => (Some(start), None, Some(default.as_bytes().to_vec())),
+                    [start, end]  => (Some(start), Some(end), Some(default.as_bytes().to_vec())),
                     _             => panic!("不明なフィールド: {}", field),
                 }
             }
@@ -138,10 +522,13 @@ impl Config {
     /// * `delimiter`  - 区切り文字
     /// * `fields`      - -fオプションで指定した出力対象フィールド
     pub fn parse_field_as_number(first_line: String, delimiter: u8, fields: String) -> Self {
-        let cols: Vec<&str> = first_line.split(char::from(delimiter)).collect();
+        let cols: Vec<&str> = Self::split_header(&first_line, delimiter);
         let mut columns: Vec<Column> = Vec::new();
         for field in fields.split(',') {
             match Self::parse_field(field) {
+                (Some(start), None, None) if Virtual::from_name(start).is_some() => { // 仮想カラム
+                    columns.push(Column::new_virtual(Virtual::from_name(start).unwrap(), Vec::new()));
+                }
                 (Some(start), None, None) => { // 範囲指定なし, デフォルト値なし
                     let idx = Self::number_to_idx(start, &cols, true);
                     columns.push(Column::new(idx, None, Vec::new()));
@@ -176,35 +563,65 @@ impl Config {
     /// * `delimiter` - 区切り文字
     /// * `fields`     - -Fオプションで指定した出力対象フィールド
     pub fn parse_field_as_name(first_line: String, delimiter: u8, fields: String) -> Self {
-        let cols: Vec<&str> = first_line.split(char::from(delimiter)).collect();
+        Self::parse_field_as_name_with(first_line, delimiter, fields, false)
+    }
+
+    /// -F オプションをパースする。カラム名は大文字小文字と前後の空白を無視して比較する
+    ///
+    /// # Arguments
+    /// * `first_line` - ファイルの1行目のヘッダ文字列
+    /// * `delimiter`  - 区切り文字
+    /// * `fields`     - -Fオプションで指定した出力対象フィールド
+    pub fn parse_field_as_name_ignore_case(first_line: String, delimiter: u8, fields: String) -> Self {
+        Self::parse_field_as_name_with(first_line, delimiter, fields, true)
+    }
+
+    fn parse_field_as_name_with(first_line: String, delimiter: u8, fields: String, ignore_case: bool) -> Self {
+        let cols: Vec<&str> = Self::split_header(&first_line, delimiter);
+        Self::warn_duplicate_names(&cols);
+        let columns = Self::resolve_fields(&cols, &fields, ignore_case);
+        Config::new(first_line, delimiter, fields, columns)
+    }
+
+    /// -F オプションと同じ形式で指定したフィールドをヘッダのカラムに解決する
+    ///
+    /// # Arguments
+    /// * `cols`        - ヘッダのカラム名の配列
+    /// * `fields`      - カンマ区切りの出力対象フィールド
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub(crate) fn resolve_fields(cols: &[&str], fields: &str, ignore_case: bool) -> Vec<Column> {
         let mut columns: Vec<Column> = Vec::new();
         for field in fields.split(',') {
             match Self::parse_field(field) {
+                (Some(start), None, None) if Virtual::from_name(start).is_some() => { // 仮想カラム
+                    let virtual_column = Virtual::from_name(start).unwrap();
+                    columns.push(Column::new_virtual(virtual_column, virtual_column.header_name().as_bytes().to_vec()));
+                }
                 (Some(start), None, None) => { // 範囲指定なし, デフォルト値なし
-                    let idx = Self::col_to_idx(start, &cols, true);
+                    let idx = Self::col_to_idx(start, cols, true, ignore_case);
                     columns.push(Column::new(idx, None, cols[idx].as_bytes().to_vec()));
                 }
                 (Some(start), None, Some(default)) => { // 範囲指定なし, デフォルト値あり
                     columns.push(Column::new(0, Some(default), start.as_bytes().to_vec()));
                 }
                 (Some(start), Some(end), None) => { // 範囲指定あり, デフォルト値なし
-                    let start = Self::col_to_idx(start, &cols, true);
-                    let end   = Self::col_to_idx(end, &cols, false);
-                    for idx in start..end {
-                        columns.push(Column::new(idx, None, cols[idx].as_bytes().to_vec()));
+                    let start = Self::col_to_idx(start, cols, true, ignore_case);
+                    let end   = Self::col_to_idx(end, cols, false, ignore_case);
+                    for (idx, col) in cols.iter().enumerate().take(end).skip(start) {
+                        columns.push(Column::new(idx, None, col.as_bytes().to_vec()));
                     }
                 }
                 (Some(start), Some(end), default) => { // 範囲指定あり, デフォルト値あり
-                    let start = Self::col_to_idx(start, &cols, true);
-                    let end   = Self::col_to_idx(end, &cols, false);
-                    for idx in start..end {
-                        columns.push(Column::new(0, default.clone(), cols[idx].as_bytes().to_vec()));
+                    let start = Self::col_to_idx(start, cols, true, ignore_case);
+                    let end   = Self::col_to_idx(end, cols, false, ignore_case);
+                    for col in cols.iter().take(end).skip(start) {
+                        columns.push(Column::new(0, default.clone(), col.as_bytes().to_vec()));
                     }
                 }
                 (_,_,_) => panic!("不正な形式のフィールドです: {}", field)
             }
         }
-        Config::new(first_line, delimiter, fields, columns)
+        columns
     }
 
     /// first_lineをヘッダとして出力する
@@ -219,7 +636,46 @@ impl Config {
         }
         buf.pop();
         buf.push(b'\n');
-        writer.write(&buf).unwrap();
+        writer.write_all(&buf).unwrap();
+    }
+
+    /// first_lineをヘッダとして出力する。重複したカラム名には「_2」「_3」のような連番を付与する
+    ///
+    /// 連番を付与した名前が他のカラム名と重複する場合は、重複しなくなるまで番号を増やす。
+    ///
+    /// # Arguments
+    /// * `writer` - ヘッダ行を書き込むwriter
+    pub fn write_unique_header<W: Write>(&self, writer: &mut W) {
+        // 各カラム名の最初の出現はそのままの名前を使う
+        let mut names: Vec<Option<Vec<u8>>> = Vec::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            let first = self.columns[..i].iter().all(|c| c.name != column.name);
+            names.push(if first { Some(column.name.clone()) } else { None });
+        }
+        for i in 0..names.len() {
+            if names[i].is_some() {
+                continue;
+            }
+            let mut n = 2;
+            let name = loop {
+                let mut name = self.columns[i].name.clone();
+                name.extend_from_slice(format!("_{}", n).as_bytes());
+                if !names.iter().any(|used| used.as_ref() == Some(&name)) {
+                    break name;
+                }
+                n += 1;
+            };
+            names[i] = Some(name);
+        }
+        let names: Vec<Vec<u8>> = names.into_iter().map(|name| name.unwrap_or_default()).collect();
+        let mut buf: Vec<u8> = Vec::new();
+        for name in names.iter() {
+            buf.extend_from_slice(name);
+            buf.push(self.delimiter);
+        }
+        buf.pop();
+        buf.push(b'\n');
+        writer.write_all(&buf).unwrap();
     }
 
     /// first_lineを以降の行と同様にパースして出力する。
@@ -228,9 +684,17 @@ impl Config {
     /// * `writer` - ヘッダ行を書き込むwriter
     pub fn write_first_line<W: Write>(&self, writer: &mut W) {
         let cols: Vec<&[u8]> = self.first_line.as_bytes().split(|e| e == &self.delimiter).collect();
+        let row = Row {
+            filename: &self.filename,
+            line_no: self.line_no - 1,
+            offset: self.header_offset,
+            line: self.first_line.as_bytes(),
+        };
         let mut buf: Vec<u8> = Vec::new();
         for column in self.columns.iter() {
-            if let Some(ref default) = column.default {
+            if let Some(ref virtual_column) = column.virtual_column {
+                virtual_column.write(&mut buf, &row);
+            } else if let Some(ref default) = column.default {
                 buf.extend_from_slice(default);
             } else {
                 buf.extend_from_slice(cols[column.idx])
@@ -239,7 +703,158 @@ impl Config {
         }
         buf.pop();
         buf.push(b'\n');
-        writer.write(&buf).unwrap();
+        writer.write_all(&buf).unwrap();
+    }
+
+    /// 出力対象カラムの解決結果を「出力位置, 入力のindex, 入力のカラム名, 固定値, 出力カラム名」のタブ区切りで出力する
+    ///
+    /// # Arguments
+    /// * `writer` - 解決結果を書き込むwriter
+    pub fn write_explain<W: Write>(&self, writer: &mut W) {
+        writeln!(writer, "position\tindex\tsource\tdefault\tname").unwrap();
+        for (position, column) in self.columns.iter().enumerate() {
+            let (index, source) = match (column.virtual_column, &column.default) {
+                (Some(v), _)    => (String::from("-"), v.spec_name().to_string()),
+                (None, Some(_)) => (String::from("-"), String::from("-")),
+                (None, None)    => (column.idx.to_string(), self.source_name(column.idx)),
+            };
+            let default = column.default.as_ref().map_or(String::from("-"), |d| String::from_utf8_lossy(d).into_owned());
+            let name = if column.name.is_empty() { String::from("-") } else { String::from_utf8_lossy(&column.name).into_owned() };
+            writeln!(writer, "{}\t{}\t{}\t{}\t{}", position, index, source, default, name).unwrap();
+        }
+    }
+
+    /// 出力対象カラムの解決結果をJSONの配列で出力する
+    ///
+    /// # Arguments
+    /// * `writer` - 解決結果を書き込むwriter
+    pub fn write_explain_json<W: Write>(&self, writer: &mut W) {
+        let mut items: Vec<String> = Vec::new();
+        for (position, column) in self.columns.iter().enumerate() {
+            let (index, source) = match (column.virtual_column, &column.default) {
+                (Some(v), _)    => (String::from("null"), util::json_string(v.spec_name())),
+                (None, Some(_)) => (String::from("null"), String::from("null")),
+                (None, None)    => (column.idx.to_string(), util::json_string(&self.source_name(column.idx))),
+            };
+            let default = column.default.as_ref().map_or(String::from("null"), |d| util::json_string(&String::from_utf8_lossy(d)));
+            let name = if column.name.is_empty() { String::from("null") } else { util::json_string(&String::from_utf8_lossy(&column.name)) };
+            items.push(format!(
+                "{{\"position\":{},\"index\":{},\"source\":{},\"default\":{},\"name\":{}}}",
+                position, index, source, default, name
+            ));
+        }
+        writeln!(writer, "[{}]", util::join(',', &items)).unwrap();
+    }
+
+    /// first_lineのidx番目のカラムの値を返す
+    fn source_name(&self, idx: usize) -> String {
+        Self::split_header(&self.first_line, self.delimiter).get(idx).map_or(String::new(), |e| e.to_string())
+    }
+}
+
+/// Configを生成するbuilder。Config::builder() で生成する
+pub struct ConfigBuilder {
+    delimiter: u8,
+    header: Option<Vec<String>>,
+    fields: Vec<Field>,
+    ignore_case: bool,
+}
+
+/// ConfigBuilderに追加された出力対象フィールド
+enum Field {
+    Column(String),
+    Constant(String, String),
+    Range(String, String),
+    Virtual(Virtual),
+}
+
+impl ConfigBuilder {
+    /// 区切り文字を指定する(デフォルト値はタブ)
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// ヘッダのカラム名を指定する
+    pub fn header<S: AsRef<str>>(mut self, header: &[S]) -> Self {
+        self.header = Some(header.iter().map(|e| e.as_ref().to_string()).collect());
+        self
+    }
+
+    /// カラム名の大文字小文字と前後の空白を無視して比較する
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    /// カラム名もしくはカラム番号で指定したカラムを出力対象に追加する
+    pub fn column(mut self, name: &str) -> Self {
+        self.fields.push(Field::Column(name.to_string()));
+        self
+    }
+
+    /// 固定値を出力するカラムを追加する
+    ///
+    /// # Arguments
+    /// * `name`  - ヘッダに出力するカラム名
+    /// * `value` - 出力する固定値
+    pub fn constant(mut self, name: &str, value: &str) -> Self {
+        self.fields.push(Field::Constant(name.to_string(), value.to_string()));
+        self
+    }
+
+    /// startからendまでのカラムを出力対象に追加する。空文字列は先頭もしくは末尾を表す
+    pub fn range(mut self, start: &str, end: &str) -> Self {
+        self.fields.push(Field::Range(start.to_string(), end.to_string()));
+        self
+    }
+
+    /// 仮想カラムを出力対象に追加する
+    pub fn virtual_column(mut self, virtual_column: Virtual) -> Self {
+        self.fields.push(Field::Virtual(virtual_column));
+        self
+    }
+
+    /// ヘッダに対して出力対象カラムを解決してConfigを生成する
+    ///
+    /// # Panics
+    /// ヘッダが指定されていない場合、出力対象カラムが指定されていない場合、
+    /// ヘッダに存在しないカラムが指定された場合
+    pub fn build(self) -> Config {
+        let header = self.header.expect("ヘッダが指定されていません");
+        if self.fields.is_empty() {
+            panic!("出力対象のカラムが指定されていません");
+        }
+        let cols: Vec<&str> = header.iter().map(|e| e.as_str()).collect();
+        let mut columns: Vec<Column> = Vec::new();
+        let mut specs: Vec<String> = Vec::new();
+        for field in self.fields.iter() {
+            match field {
+                Field::Column(name) => {
+                    let idx = Config::col_to_idx(name, &cols, true, self.ignore_case);
+                    columns.push(Column::new(idx, None, cols[idx].as_bytes().to_vec()));
+                    specs.push(name.clone());
+                }
+                Field::Constant(name, value) => {
+                    columns.push(Column::new(0, Some(value.as_bytes().to_vec()), name.as_bytes().to_vec()));
+                    specs.push(format!("{}:{}", name, value));
+                }
+                Field::Range(start, end) => {
+                    let start_idx = Config::col_to_idx(start, &cols, true, self.ignore_case);
+                    let end_idx   = Config::col_to_idx(end, &cols, false, self.ignore_case);
+                    for (idx, col) in cols.iter().enumerate().take(end_idx).skip(start_idx) {
+                        columns.push(Column::new(idx, None, col.as_bytes().to_vec()));
+                    }
+                    specs.push(format!("{}..{}", start, end));
+                }
+                Field::Virtual(virtual_column) => {
+                    columns.push(Column::new_virtual(*virtual_column, virtual_column.header_name().as_bytes().to_vec()));
+                    specs.push(virtual_column.spec_name().to_string());
+                }
+            }
+        }
+        let first_line = util::join(char::from(self.delimiter), &header);
+        Config::new(first_line, self.delimiter, util::join(',', &specs), columns)
     }
 }
 
@@ -248,14 +863,77 @@ pub struct Column {
     pub idx: usize,
     pub default: Option<Vec<u8>>,
     pub name: Vec<u8>,
+    pub virtual_column: Option<Virtual>,
 }
 
 impl Column {
     pub fn new(idx: usize, default: Option<Vec<u8>>, name: Vec<u8>) -> Self {
-        Column { idx, default, name }
+        Column { idx, default, name, virtual_column: None }
+    }
+
+    pub fn new_virtual(virtual_column: Virtual, name: Vec<u8>) -> Self {
+        Column { idx: 0, default: None, name, virtual_column: Some(virtual_column) }
+    }
+}
+
+/// 入力データではなく行ごとのメタ情報を出力する仮想カラム
+#[derive(PartialEq,Debug,Clone,Copy)]
+pub enum Virtual {
+    /// 行番号(1始まり)
+    LineNo,
+    /// 入力ファイル名
+    FileName,
+    /// 改行を除いた行のハッシュ値(FNV-1a 64bit)
+    RowHash,
+    /// ファイル先頭から行頭までのバイト数
+    BytesOffset,
+}
+
+impl Virtual {
+    /// 「@lineno」のようなフィールド指定を仮想カラムに変換する
+    pub fn from_name(name: &str) -> Option<Virtual> {
+        match name {
+            "@lineno"       => Some(Virtual::LineNo),
+            "@filename"     => Some(Virtual::FileName),
+            "@rowhash"      => Some(Virtual::RowHash),
+            "@bytes_offset" => Some(Virtual::BytesOffset),
+            _               => None,
+        }
+    }
+
+    /// フィールド指定での名前
+    pub fn spec_name(self) -> &'static str {
+        match self {
+            Virtual::LineNo      => "@lineno",
+            Virtual::FileName    => "@filename",
+            Virtual::RowHash     => "@rowhash",
+            Virtual::BytesOffset => "@bytes_offset",
+        }
+    }
+
+    /// ヘッダに出力するカラム名
+    pub fn header_name(self) -> &'static str {
+        &self.spec_name()[1..]
+    }
+
+    fn write<W: Write>(self, writer: &mut W, row: &Row) {
+        match self {
+            Virtual::LineNo      => write!(writer, "{}", row.line_no).unwrap(),
+            Virtual::FileName    => writer.write_all(row.filename.as_bytes()).unwrap(),
+            Virtual::RowHash     => write!(writer, "{:016x}", util::fnv1a64(row.line)).unwrap(),
+            Virtual::BytesOffset => write!(writer, "{}", row.offset).unwrap(),
+        }
     }
 }
 
+/// 仮想カラムの値を求めるための行の情報
+struct Row<'a> {
+    filename: &'a str,
+    line_no: usize,
+    offset: u64,
+    line: &'a [u8],
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -266,11 +944,11 @@ mod tests {
         let col_name_list = vec!["", "col1", "2"];
         let expected = [0, 1, 2];
         for (i, col_name) in col_name_list.iter().enumerate() {
-            assert_eq!(expected[i], Config::col_to_idx(col_name, &header, true));
+            assert_eq!(expected[i], Config::col_to_idx(col_name, &header, true, false));
         }
         let expected = [3, 2, 3];
         for (i, col_name) in col_name_list.iter().enumerate() {
-            assert_eq!(expected[i], Config::col_to_idx(col_name, &header, false));
+            assert_eq!(expected[i], Config::col_to_idx(col_name, &header, false, false));
         }
     }
 
@@ -279,7 +957,7 @@ mod tests {
     fn test_col_to_idx_2() {
         let header = vec!["col0", "col1", "col2"];
         let col_name = "col3";
-        Config::col_to_idx(col_name, &header, true);
+        Config::col_to_idx(col_name, &header, true, false);
     }
 
     #[test]
@@ -287,7 +965,7 @@ mod tests {
     fn test_col_to_idx_3() {
         let header = vec!["col0", "col1", "col2"];
         let col_name = "100";
-        Config::col_to_idx(col_name, &header, true);
+        Config::col_to_idx(col_name, &header, true, false);
     }
 
     #[test]
@@ -295,7 +973,45 @@ mod tests {
     fn test_col_to_idx_4() {
         let header = vec!["col0", "col1", "col2"];
         let col_name = "-100";
-        Config::col_to_idx(col_name, &header, true);
+        Config::col_to_idx(col_name, &header, true, false);
+    }
+
+    #[test]
+    fn test_col_to_idx_5() {
+        // 重複したカラム名は「カラム名#n」で指定できる
+        let header = vec!["id", "title", "id", "id#2"];
+        assert_eq!(0, Config::col_to_idx("id", &header, true, false));
+        assert_eq!(0, Config::col_to_idx("id#1", &header, true, false));
+        // 「id#2」というカラム名が存在する場合はそちらを優先する
+        assert_eq!(3, Config::col_to_idx("id#2", &header, true, false));
+        assert_eq!(None, Config::qualified_col_to_idx("id#3", &header, false));
+        assert_eq!(None, Config::qualified_col_to_idx("id#0", &header, false));
+        assert_eq!(Some(2), Config::qualified_col_to_idx("id#2", &header, false));
+    }
+
+    #[test]
+    #[should_panic(expected = "不明なフィールド: title#2")]
+    fn test_col_to_idx_6() {
+        let header = vec!["id", "title", "id"];
+        Config::col_to_idx("title#2", &header, true, false);
+    }
+
+    #[test]
+    fn test_col_to_idx_7() {
+        // 大文字小文字と前後の空白を無視する
+        let header = vec!["ID", " Title ", "id"];
+        assert_eq!(1, Config::col_to_idx("title", &header, true, true));
+        assert_eq!(0, Config::col_to_idx("iD", &header, true, true));
+        // 完全に一致するカラム名を優先する
+        assert_eq!(2, Config::col_to_idx("id", &header, true, true));
+        assert_eq!(2, Config::col_to_idx("Id#2", &header, true, true));
+    }
+
+    #[test]
+    #[should_panic(expected = "不明なフィールド: titel\n  もしかして: title\n  利用可能なカラム: 0:id,1:title,2:kana")]
+    fn test_col_to_idx_8() {
+        let header = vec!["id", "title", "kana"];
+        Config::col_to_idx("titel", &header, true, true);
     }
 
     #[test]
@@ -342,7 +1058,7 @@ mod tests {
             ""     , "col0"     , "col0..col3"     , "col0.."     , "..col3"     , "..",
             ":def1", "col0:def1", "col0..col3:def1", "col0..:def1", "..col3:def1", "..:def1",
         ];
-        let expected: Vec<(Option<&str>, Option<&str>, Option<Vec<u8>>)> = vec![
+        let expected = vec![
             (Some("")    , None        , None),
             (Some("col0"), None        , None),
             (Some("col0"), Some("col3"), None),
@@ -556,6 +1272,146 @@ mod tests {
         assert_eq!(expected, cfg.columns);
     }
 
+    #[test]
+    fn test_parse_field_as_name_8() {
+        let field = String::from("id#2,title,id..title,id#2..");
+        let header = String::from("id,title,id,kana");
+        let cfg = Config::parse_field_as_name(header, b',', field);
+        let expected: Vec<Column> = vec![
+            Column::new(2, None , b"id".to_vec()),
+            Column::new(1, None , b"title".to_vec()),
+            Column::new(0, None , b"id".to_vec()),
+            Column::new(1, None , b"title".to_vec()),
+            Column::new(2, None , b"id".to_vec()),
+            Column::new(3, None , b"kana".to_vec()),
+        ];
+        assert_eq!(expected, cfg.columns);
+        let mut writer: Vec<u8> = Vec::new();
+        cfg.write_unique_header(&mut writer);
+        assert_eq!(b"id,title,id_2,title_2,id_3,kana\n".to_vec(), writer);
+    }
+
+    #[test]
+    fn test_write_unique_header_1() {
+        // 連番を付与した名前が既存のカラム名と重複する場合は番号を増やす
+        let cases = vec![
+            ("a,a,a_2", "a,a_3,a_2\n"),
+            ("a,a_2,a,a", "a,a_2,a_3,a_4\n"),
+            ("a,a,a_2,a_2", "a,a_3,a_2,a_2_2\n"),
+        ];
+        for (header, expected) in cases {
+            let cfg = Config::parse_field_as_name(String::from(header), b',', String::from(".."));
+            let mut writer: Vec<u8> = Vec::new();
+            cfg.write_unique_header(&mut writer);
+            assert_eq!(expected, String::from_utf8(writer).unwrap(), "header: {}", header);
+        }
+    }
+
+    #[test]
+    fn test_write_explain_1() {
+        let field = String::from("title,src:0,id#2");
+        let header = String::from("id,title,id");
+        let cfg = Config::parse_field_as_name(header, b',', field);
+        let mut writer: Vec<u8> = Vec::new();
+        cfg.write_explain(&mut writer);
+        let expected = "position\tindex\tsource\tdefault\tname\n\
+                        0\t1\ttitle\t-\ttitle\n\
+                        1\t-\t-\t0\tsrc\n\
+                        2\t2\tid\t-\tid\n";
+        assert_eq!(expected, String::from_utf8(writer).unwrap());
+    }
+
+    #[test]
+    fn test_write_explain_json_1() {
+        let field = String::from("1,:\"foo\"");
+        let header = String::from("id,title");
+        let cfg = Config::parse_field_as_number(header, b',', field);
+        let mut writer: Vec<u8> = Vec::new();
+        cfg.write_explain_json(&mut writer);
+        let expected = concat!(
+            "[{\"position\":0,\"index\":1,\"source\":\"title\",\"default\":null,\"name\":null},",
+            "{\"position\":1,\"index\":null,\"source\":null,\"default\":\"\\\"foo\\\"\",\"name\":null}]\n",
+        );
+        assert_eq!(expected, String::from_utf8(writer).unwrap());
+    }
+
+    #[test]
+    fn test_parse_field_as_name_9() {
+        let field = String::from("@lineno,title,@filename,@rowhash,@bytes_offset,@lineno:foo");
+        let header = String::from("id,title");
+        let cfg = Config::parse_field_as_name(header, b',', field);
+        let expected: Vec<Column> = vec![
+            Column::new_virtual(Virtual::LineNo, b"lineno".to_vec()),
+            Column::new(1, None, b"title".to_vec()),
+            Column::new_virtual(Virtual::FileName, b"filename".to_vec()),
+            Column::new_virtual(Virtual::RowHash, b"rowhash".to_vec()),
+            Column::new_virtual(Virtual::BytesOffset, b"bytes_offset".to_vec()),
+            Column::new(0, Some(b"foo".to_vec()), b"@lineno".to_vec()),
+        ];
+        assert_eq!(expected, cfg.columns);
+    }
+
+    #[test]
+    #[should_panic(expected = "不明なフィールド: @lineno")]
+    fn test_parse_field_as_name_10() {
+        // 仮想カラムは範囲指定できない
+        let field = String::from("@lineno..title");
+        let header = String::from("id,title");
+        Config::parse_field_as_name(header, b',', field);
+    }
+
+    #[test]
+    fn test_builder_1() {
+        // 文字列のフィールド指定と同じ結果になる
+        let header = vec!["itemid", "title", "url", "desc", "keyword1", "keyword2", "narrow1", "narrow2"];
+        let cfg = Config::builder()
+            .delimiter(b',')
+            .header(&header)
+            .column("title")
+            .constant("src", "0")
+            .range("url", "keyword1")
+            .range("", "1")
+            .range("narrow1", "")
+            .column("6")
+            .virtual_column(Virtual::LineNo)
+            .build();
+        let expected = Config::parse_field_as_name(
+            header.join(","), b',', String::from("title,src:0,url..keyword1,..1,narrow1..,6,@lineno")
+        );
+        assert_eq!(expected.columns, cfg.columns);
+        assert_eq!(expected.field, cfg.field);
+        assert_eq!(expected.first_line, cfg.first_line);
+        assert_eq!(b',', cfg.delimiter);
+    }
+
+    #[test]
+    fn test_builder_2() {
+        let cfg = Config::builder()
+            .header(&[String::from("ID"), String::from("Title")])
+            .ignore_case(true)
+            .column("title")
+            .column("id")
+            .build();
+        let expected: Vec<Column> = vec![
+            Column::new(1, None, b"Title".to_vec()),
+            Column::new(0, None, b"ID".to_vec()),
+        ];
+        assert_eq!(expected, cfg.columns);
+        assert_eq!("ID\tTitle", cfg.first_line);
+    }
+
+    #[test]
+    #[should_panic(expected = "不明なフィールド: not_exists")]
+    fn test_builder_3() {
+        Config::builder().header(&["id", "title"]).column("id").range("title", "not_exists").build();
+    }
+
+    #[test]
+    #[should_panic(expected = "ヘッダが指定されていません")]
+    fn test_builder_4() {
+        Config::builder().column("id").build();
+    }
+
     #[test]
     #[should_panic(expected = "不明なフィールド: not_exists")]
     fn test_parse_field_as_name_4() {
@@ -0,0 +1,584 @@
+//! 2つのファイルをヘッダのカラム名で指定したキーで結合する
+use crate::mcut;
+use crate::reader::{Reader, Record, RecordBuf};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::prelude::*;
+
+/// 結合の種類
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum JoinType {
+    /// 両方のファイルにキーが存在する行のみ出力する
+    Inner,
+    /// 左側のファイルの行は全て出力する
+    Left,
+    /// 右側のファイルの行は全て出力する
+    Right,
+    /// 両方のファイルの行を全て出力する
+    Full,
+    /// 右側のファイルにキーが存在する左側のファイルの行をそのまま出力する
+    Semi,
+    /// 右側のファイルにキーが存在しない左側のファイルの行をそのまま出力する
+    Anti,
+}
+
+impl JoinType {
+    /// inner, left, right, full, semi, anti のいずれかの名前から結合の種類を返す
+    ///
+    /// # Arguments
+    /// * `name` - 結合の種類の名前
+    pub fn from_name(name: &str) -> Option<JoinType> {
+        match name {
+            "inner" => Some(JoinType::Inner),
+            "left"  => Some(JoinType::Left),
+            "right" => Some(JoinType::Right),
+            "full"  => Some(JoinType::Full),
+            "semi"  => Some(JoinType::Semi),
+            "anti"  => Some(JoinType::Anti),
+            _       => None,
+        }
+    }
+
+    /// 結合相手のいない行を出力するかどうかを返す
+    fn keeps(self, side: Side) -> bool {
+        match (self, side) {
+            (JoinType::Full, _) => true,
+            (JoinType::Left, Side::Left) | (JoinType::Right, Side::Right) | (JoinType::Anti, Side::Left) => true,
+            _ => false,
+        }
+    }
+
+    /// 左側のファイルの行をそのまま出力する結合(semi, anti)かどうかを返す
+    pub fn is_filter(self) -> bool {
+        self == JoinType::Semi || self == JoinType::Anti
+    }
+}
+
+/// 結合するファイルの左右
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// 結合のアルゴリズム
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Algorithm {
+    /// 一方のファイルでハッシュテーブルを構築する
+    Hash,
+    /// キーでソート済みの両方のファイルを先頭から順に読み込んで結合する
+    Merge,
+}
+
+/// 出力するカラムの値の取得元
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    /// 左右どちらかのファイルのカラム。結合相手のいない側のカラムは空文字列を出力する
+    Column(Side, usize),
+    /// n番目のキー。左側のファイルの値を出力し、左側の行がない場合は右側のファイルの値を出力する
+    Key(usize),
+    /// 固定値
+    Constant(Vec<u8>),
+}
+
+/// 結合の設定
+pub struct Config {
+    pub delimiter: u8,
+    pub join_type: JoinType,
+    pub algorithm: Algorithm,
+    /// 左側のファイルのヘッダのカラム名の配列
+    pub left_header: Vec<String>,
+    /// 右側のファイルのヘッダのカラム名の配列
+    pub right_header: Vec<String>,
+    /// 左側のファイルのキーのカラム番号
+    pub left_keys: Vec<usize>,
+    /// 右側のファイルのキーのカラム番号
+    pub right_keys: Vec<usize>,
+    /// 出力するカラムの(カラム名, 値の取得元)
+    pub columns: Vec<(Vec<u8>, Output)>,
+    /// ハッシュテーブルを構築する側。もう一方のファイルは1行ずつ読み込んで結合する
+    pub build_side: Side,
+    /// この文字列で始まる行はコメント行とみなし、結合しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// 両方のファイルのヘッダ行とキーの指定からConfigを生成する
+    ///
+    /// 出力するカラムは、左側のファイルの全てのカラム(キーは左右で値のある方)と右側のファイルのキー以外のカラムとする。
+    ///
+    /// # Arguments
+    /// * `left_line`   - 左側のファイルのヘッダ行
+    /// * `right_line`  - 右側のファイルのヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `left_keys`   - 左側のファイルのキーのカラム名もしくはカラム番号(カンマ区切り)
+    /// * `right_keys`  - 右側のファイルのキーのカラム名もしくはカラム番号(カンマ区切り)
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn new(left_line: &str, right_line: &str, delimiter: u8, left_keys: &str, right_keys: &str, ignore_case: bool) -> Self {
+        let left_header: Vec<&str> = mcut::Config::split_header(left_line, delimiter);
+        let right_header: Vec<&str> = mcut::Config::split_header(right_line, delimiter);
+        let left_keys = Self::resolve_keys(left_keys, &left_header, ignore_case);
+        let right_keys = Self::resolve_keys(right_keys, &right_header, ignore_case);
+        if left_keys.len() != right_keys.len() {
+            panic!("左右のキーの数が一致しません: {} 個と {} 個", left_keys.len(), right_keys.len());
+        }
+        let mut columns: Vec<(Vec<u8>, Output)> = Vec::new();
+        for (idx, name) in left_header.iter().enumerate() {
+            match left_keys.iter().position(|key| *key == idx) {
+                Some(n) => columns.push((name.as_bytes().to_vec(), Output::Key(n))),
+                None    => columns.push((name.as_bytes().to_vec(), Output::Column(Side::Left, idx))),
+            }
+        }
+        for (idx, name) in right_header.iter().enumerate() {
+            if !right_keys.contains(&idx) {
+                columns.push((name.as_bytes().to_vec(), Output::Column(Side::Right, idx)));
+            }
+        }
+        Config {
+            delimiter,
+            join_type: JoinType::Inner,
+            algorithm: Algorithm::Hash,
+            left_header: left_header.iter().map(|e| e.to_string()).collect(),
+            right_header: right_header.iter().map(|e| e.to_string()).collect(),
+            left_keys,
+            right_keys,
+            columns,
+            build_side: Side::Right,
+            comment_prefix: None,
+        }
+    }
+
+    fn resolve_keys(keys: &str, header: &[&str], ignore_case: bool) -> Vec<usize> {
+        keys.split(',').map(|key| {
+            mcut::Config::find_column(key, header, ignore_case).unwrap_or_else(|| {
+                panic!("不明なフィールド: {}{}", key, mcut::Config::unknown_field_hint(key, header));
+            })
+        }).collect()
+    }
+
+    /// 出力するカラムを -F オプションと同じ形式で指定する
+    ///
+    /// カラムは「L.カラム名」「R.カラム名」のように左右どちらのファイルのカラムかを指定する。
+    /// 「L.id..title」のような範囲指定、「カラム名:固定値」も指定できる。
+    /// L., R. を省略した場合は左側のファイル、右側のファイルの順にカラムを探す。
+    ///
+    /// # Arguments
+    /// * `fields`      - 出力するカラム。例) L.id,L.title,R.price
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn set_fields(&mut self, fields: &str, ignore_case: bool) {
+        let left_header: Vec<&str> = self.left_header.iter().map(|e| e.as_str()).collect();
+        let right_header: Vec<&str> = self.right_header.iter().map(|e| e.as_str()).collect();
+        let mut columns = Vec::new();
+        for field in fields.split(',') {
+            let (side, spec) = if field.starts_with("L.") {
+                (Side::Left, &field[2..])
+            } else if field.starts_with("R.") {
+                (Side::Right, &field[2..])
+            } else {
+                let name = field.split(|c| c == ':').next().unwrap().split("..").next().unwrap();
+                let in_left = mcut::Config::find_column(name, &left_header, ignore_case).is_some();
+                let in_right = mcut::Config::find_column(name, &right_header, ignore_case).is_some();
+                (if !in_left && in_right { Side::Right } else { Side::Left }, field)
+            };
+            let header = if side == Side::Left { &left_header } else { &right_header };
+            for column in mcut::Config::resolve_fields(header, spec, ignore_case) {
+                if column.virtual_column.is_some() {
+                    panic!("仮想カラムは指定できません: {}", field);
+                }
+                let output = match column.default {
+                    Some(value) => Output::Constant(value),
+                    None        => Output::Column(side, column.idx),
+                };
+                columns.push((column.name, output));
+            }
+        }
+        self.columns = columns;
+    }
+
+    /// 出力するカラムのカラム名をヘッダとして出力する。semi, anti の場合は左側のファイルのヘッダを出力する
+    ///
+    /// # Arguments
+    /// * `writer` - ヘッダ行を書き込むwriter
+    pub fn write_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let names: Vec<&[u8]> = if self.join_type.is_filter() {
+            self.left_header.iter().map(|name| name.as_bytes()).collect()
+        } else {
+            self.columns.iter().map(|(name, _)| &name[..]).collect()
+        };
+        writer.write_all(&names.join(&self.delimiter))?;
+        writer.write_all(b"\n")
+    }
+
+    fn keys(&self, side: Side) -> &[usize] {
+        match side {
+            Side::Left  => &self.left_keys,
+            Side::Right => &self.right_keys,
+        }
+    }
+
+    /// 読み込み元のファイルの区切り文字とコメント行の設定でreaderを生成する
+    fn reader<R: BufRead>(&self, reader: R) -> Reader<R> {
+        Reader::new(reader, self.delimiter).with_comment_prefix(self.comment_prefix.as_ref().map(|prefix| &prefix[..]))
+    }
+
+    /// 行のキーを区切り文字で連結したバイト列を返す。カラムが足りない場合は空文字列とみなす
+    fn key(&self, record: &Record, side: Side) -> Vec<u8> {
+        let values: Vec<&[u8]> = self.keys(side).iter().map(|idx| record.field(*idx)).collect();
+        values.join(&self.delimiter)
+    }
+
+    /// 行のキーの値の配列を返す。カラムが足りない場合は空文字列とみなす
+    fn key_values(&self, record: &Record, side: Side) -> Vec<Vec<u8>> {
+        self.keys(side).iter().map(|idx| record.field(*idx).to_vec()).collect()
+    }
+
+    /// 結合相手のいない左側のファイルの行を出力する。semi, anti の場合は行をそのまま出力する
+    fn write_left<W: Write>(&self, writer: &mut W, record: &Record) -> io::Result<()> {
+        if self.join_type.is_filter() {
+            writer.write_all(record.as_bytes())?;
+            writer.write_all(b"\n")
+        } else {
+            self.write_row(writer, Some(record), None)
+        }
+    }
+
+    /// 結合した1行を出力する。カラムが足りない場合は空文字列とみなす
+    fn write_row<W: Write>(&self, writer: &mut W, left: Option<&Record>, right: Option<&Record>) -> io::Result<()> {
+        for (i, (_, output)) in self.columns.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(&[self.delimiter])?;
+            }
+            let value: &[u8] = match output {
+                Output::Column(Side::Left, idx)  => left.map_or(b"", |record| record.field(*idx)),
+                Output::Column(Side::Right, idx) => right.map_or(b"", |record| record.field(*idx)),
+                Output::Key(n) => match (left, right) {
+                    (Some(record), _) => record.field(self.left_keys[*n]),
+                    (None, Some(record)) => record.field(self.right_keys[*n]),
+                    (None, None) => b"",
+                },
+                Output::Constant(value) => value,
+            };
+            writer.write_all(value)?;
+        }
+        writer.write_all(b"\n")
+    }
+}
+
+/// ヘッダ行を読み込んだ後の2つのファイルをcfgの設定に従って結合し、writerに書き込む
+///
+/// cfg.algorithm が Hash の場合は、cfg.build_side のファイルを全て読み込んでハッシュテーブルを構築し、
+/// もう一方のファイルを1行ずつ読み込んで結合する。
+/// 出力順はもう一方のファイルの行の順番とし、結合相手のいない cfg.build_side のファイルの行は最後に出力する。
+///
+/// cfg.algorithm が Merge の場合は、キーで(バイト列として)昇順にソート済みの両方のファイルを先頭から順に読み込んで結合する。
+/// メモリに保持するのは右側のファイルの同じキーの行のみとなる。
+/// キーの順番が前の行より小さい行を読み込んだ場合は io::ErrorKind::InvalidData のエラーを返す。
+///
+/// # Arguments
+/// * `left`   - 左側のファイル
+/// * `right`  - 右側のファイル
+/// * `writer`
+/// * `cfg`    - キーや出力するカラムの設定
+pub fn mjoin<L: BufRead, R: BufRead, W: Write>(left: &mut L, right: &mut R, writer: &mut W, cfg: &Config) -> io::Result<()> {
+    match (cfg.algorithm, cfg.build_side) {
+        (Algorithm::Merge, _)          => merge_join(left, right, writer, cfg),
+        (Algorithm::Hash, _) if cfg.join_type.is_filter() => semi_join(left, right, writer, cfg),
+        (Algorithm::Hash, Side::Left)  => hash_join(left, right, writer, cfg, Side::Left),
+        (Algorithm::Hash, Side::Right) => hash_join(right, left, writer, cfg, Side::Right),
+    }
+}
+
+fn hash_join<B: BufRead, P: BufRead, W: Write>(build: &mut B, probe: &mut P, writer: &mut W, cfg: &Config, build_side: Side) -> io::Result<()> {
+    let probe_side = if build_side == Side::Left { Side::Right } else { Side::Left };
+    // ハッシュテーブルを構築する側の行と、キーから行番号の一覧へのハッシュテーブル
+    let mut rows: Vec<RecordBuf> = Vec::new();
+    let mut table: HashMap<Vec<u8>, Vec<usize>> = HashMap::new();
+    let mut build = cfg.reader(build);
+    while let Some(record) = build.read_record()? {
+        table.entry(cfg.key(&record, build_side)).or_insert_with(Vec::new).push(rows.len());
+        rows.push(record.to_buf());
+    }
+    let mut matched = vec![false; rows.len()];
+    let mut probe = cfg.reader(probe);
+    while let Some(record) = probe.read_record()? {
+        match table.get(&cfg.key(&record, probe_side)) {
+            Some(indices) => {
+                for idx in indices {
+                    matched[*idx] = true;
+                    let build_record = rows[*idx].as_record();
+                    match build_side {
+                        Side::Left  => cfg.write_row(writer, Some(&build_record), Some(&record))?,
+                        Side::Right => cfg.write_row(writer, Some(&record), Some(&build_record))?,
+                    }
+                }
+            }
+            None if cfg.join_type.keeps(probe_side) => match probe_side {
+                Side::Left  => cfg.write_row(writer, Some(&record), None)?,
+                Side::Right => cfg.write_row(writer, None, Some(&record))?,
+            },
+            None => {}
+        }
+    }
+    if cfg.join_type.keeps(build_side) {
+        for (row, _) in rows.iter().zip(&matched).filter(|(_, matched)| !**matched) {
+            match build_side {
+                Side::Left  => cfg.write_row(writer, Some(&row.as_record()), None)?,
+                Side::Right => cfg.write_row(writer, None, Some(&row.as_record()))?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 右側のファイルのキーのハッシュセットを構築し、左側のファイルを1行ずつ読み込んでキーの有無で絞り込む
+fn semi_join<L: BufRead, R: BufRead, W: Write>(left: &mut L, right: &mut R, writer: &mut W, cfg: &Config) -> io::Result<()> {
+    let mut keys: HashSet<Vec<u8>> = HashSet::new();
+    let mut right = cfg.reader(right);
+    while let Some(record) = right.read_record()? {
+        keys.insert(cfg.key(&record, Side::Right));
+    }
+    let semi = cfg.join_type == JoinType::Semi;
+    let mut left = cfg.reader(left);
+    while let Some(record) = left.read_record()? {
+        if keys.contains(&cfg.key(&record, Side::Left)) == semi {
+            writer.write_all(record.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+fn merge_join<L: BufRead, R: BufRead, W: Write>(left: &mut L, right: &mut R, writer: &mut W, cfg: &Config) -> io::Result<()> {
+    let mut left = SortedReader::new(left, Side::Left, cfg)?;
+    let mut right = SortedReader::new(right, Side::Right, cfg)?;
+    // 右側のファイルの同じキーの行
+    let mut group: Vec<RecordBuf> = Vec::new();
+    loop {
+        let ord = match (&left.current, &right.current) {
+            (None, None)       => return Ok(()),
+            (Some(_), None)    => Ordering::Less,
+            (None, Some(_))    => Ordering::Greater,
+            (Some((_, l)), Some((_, r))) => l.cmp(r),
+        };
+        match ord {
+            Ordering::Less => {
+                if cfg.join_type.keeps(Side::Left) {
+                    cfg.write_left(writer, &left.record())?;
+                }
+                left.advance()?;
+            }
+            Ordering::Greater => {
+                if cfg.join_type.keeps(Side::Right) {
+                    cfg.write_row(writer, None, Some(&right.record()))?;
+                }
+                right.advance()?;
+            }
+            Ordering::Equal => {
+                let key = left.key().to_vec();
+                group.clear();
+                while right.current.as_ref().map_or(false, |(_, k)| *k == key) {
+                    group.push(right.current.as_ref().unwrap().0.clone());
+                    right.advance()?;
+                }
+                while left.current.as_ref().map_or(false, |(_, k)| *k == key) {
+                    match cfg.join_type {
+                        JoinType::Semi => cfg.write_left(writer, &left.record())?,
+                        JoinType::Anti => {}
+                        _ => {
+                            let record = left.record();
+                            for row in &group {
+                                cfg.write_row(writer, Some(&record), Some(&row.as_record()))?;
+                            }
+                        }
+                    }
+                    left.advance()?;
+                }
+            }
+        }
+    }
+}
+
+/// キーでソート済みのファイルを1行ずつ読み込み、キーの順番を検証するreader
+struct SortedReader<'a, R> {
+    reader: Reader<&'a mut R>,
+    side: Side,
+    cfg: &'a Config,
+    /// 最後に読み込んだ(行, キー)。ファイルの末尾に達した場合はNone
+    current: Option<(RecordBuf, Vec<Vec<u8>>)>,
+    /// ヘッダを除いた行数
+    row_count: usize,
+}
+
+impl<'a, R: BufRead> SortedReader<'a, R> {
+    fn new(reader: &'a mut R, side: Side, cfg: &'a Config) -> io::Result<Self> {
+        let mut ret = SortedReader { reader: cfg.reader(reader), side, cfg, current: None, row_count: 0 };
+        ret.advance()?;
+        Ok(ret)
+    }
+
+    fn record(&self) -> Record {
+        self.current.as_ref().unwrap().0.as_record()
+    }
+
+    fn key(&self) -> &[Vec<u8>] {
+        &self.current.as_ref().unwrap().1
+    }
+
+    /// 次の行を読み込む
+    fn advance(&mut self) -> io::Result<()> {
+        let record = match self.reader.read_record()? {
+            Some(record) => record,
+            None => {
+                self.current = None;
+                return Ok(());
+            }
+        };
+        self.row_count += 1;
+        let key = self.cfg.key_values(&record, self.side);
+        let record = record.to_buf();
+        if let Some((_, prev)) = &self.current {
+            if key < *prev {
+                let side = if self.side == Side::Left { "左側" } else { "右側" };
+                let show = |key: &[Vec<u8>]| String::from_utf8_lossy(&key.join(&self.cfg.delimiter)).into_owned();
+                let message = format!("{}のファイルがキーでソートされていません: ヘッダを除いた{}行目のキー {} が前の行のキー {} より小さい値です",
+                                      side, self.row_count, show(&key), show(prev));
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+        self.current = Some((record, key));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: &str = "id,title\n1,東京都\n2,大阪府\n3,茨城県\n2,おおさか\n";
+    const RIGHT: &str = "pref_id,price\n2,200\n1,100\n4,400\n2,250\n";
+
+    fn join(cfg: &Config) -> String {
+        let mut left = LEFT.as_bytes();
+        let mut right = RIGHT.as_bytes();
+        let mut line = String::new();
+        left.read_line(&mut line).unwrap();
+        right.read_line(&mut line).unwrap();
+        let mut writer = Vec::new();
+        cfg.write_header(&mut writer).unwrap();
+        mjoin(&mut left, &mut right, &mut writer, cfg).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    fn config(join_type: JoinType, build_side: Side) -> Config {
+        let mut cfg = Config::new("id,title", "pref_id,price", b',', "id", "pref_id", false);
+        cfg.join_type = join_type;
+        cfg.build_side = build_side;
+        cfg
+    }
+
+    #[test]
+    fn test_new() {
+        let cfg = config(JoinType::Inner, Side::Right);
+        assert_eq!((vec![0], vec![0]), (cfg.left_keys.clone(), cfg.right_keys.clone()));
+        let expected = vec![
+            (b"id".to_vec(), Output::Key(0)),
+            (b"title".to_vec(), Output::Column(Side::Left, 1)),
+            (b"price".to_vec(), Output::Column(Side::Right, 1)),
+        ];
+        assert_eq!(expected, cfg.columns);
+    }
+
+    #[test]
+    #[should_panic(expected = "左右のキーの数が一致しません: 2 個と 1 個")]
+    fn test_new_key_mismatch() {
+        Config::new("id,title", "pref_id,price", b',', "id,title", "pref_id", false);
+    }
+
+    #[test]
+    fn test_mjoin_inner() {
+        let expected = "id,title,price\n1,東京都,100\n2,大阪府,200\n2,大阪府,250\n2,おおさか,200\n2,おおさか,250\n";
+        assert_eq!(expected, join(&config(JoinType::Inner, Side::Right)));
+        // ハッシュテーブルを左側で構築した場合は右側の順に出力する
+        let expected = "id,title,price\n2,大阪府,200\n2,おおさか,200\n1,東京都,100\n2,大阪府,250\n2,おおさか,250\n";
+        assert_eq!(expected, join(&config(JoinType::Inner, Side::Left)));
+    }
+
+    #[test]
+    fn test_mjoin_outer() {
+        let expected = "id,title,price\n1,東京都,100\n2,大阪府,200\n2,大阪府,250\n3,茨城県,\n2,おおさか,200\n2,おおさか,250\n";
+        assert_eq!(expected, join(&config(JoinType::Left, Side::Right)));
+        let expected = "id,title,price\n2,大阪府,200\n2,おおさか,200\n1,東京都,100\n4,,400\n2,大阪府,250\n2,おおさか,250\n";
+        assert_eq!(expected, join(&config(JoinType::Right, Side::Left)));
+        let expected = "id,title,price\n1,東京都,100\n2,大阪府,200\n2,大阪府,250\n3,茨城県,\n2,おおさか,200\n2,おおさか,250\n4,,400\n";
+        assert_eq!(expected, join(&config(JoinType::Full, Side::Right)));
+    }
+
+    fn merge(left: &str, right: &str, join_type: JoinType) -> io::Result<String> {
+        let mut cfg = Config::new("id,title", "pref_id,price", b',', "id", "pref_id", false);
+        cfg.join_type = join_type;
+        cfg.algorithm = Algorithm::Merge;
+        let mut writer = Vec::new();
+        mjoin(&mut left.as_bytes(), &mut right.as_bytes(), &mut writer, &cfg)?;
+        Ok(String::from_utf8(writer).unwrap())
+    }
+
+    #[test]
+    fn test_merge_join() {
+        let left = "1,東京都\n2,大阪府\n2,おおさか\n3,茨城県\n";
+        let right = "0,0\n2,200\n2,250\n3,300\n4,400\n";
+        let expected = "2,大阪府,200\n2,大阪府,250\n2,おおさか,200\n2,おおさか,250\n3,茨城県,300\n";
+        assert_eq!(expected, merge(left, right, JoinType::Inner).unwrap());
+        let expected = "0,,0\n1,東京都,\n2,大阪府,200\n2,大阪府,250\n2,おおさか,200\n2,おおさか,250\n3,茨城県,300\n4,,400\n";
+        assert_eq!(expected, merge(left, right, JoinType::Full).unwrap());
+        assert_eq!("1,東京都,\n2,大阪府,\n", merge("1,東京都\n2,大阪府\n", "", JoinType::Left).unwrap());
+    }
+
+    #[test]
+    fn test_merge_join_unsorted() {
+        let err = merge("1,東京都\n3,茨城県\n2,大阪府\n", "1,100\n", JoinType::Inner).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert_eq!("左側のファイルがキーでソートされていません: ヘッダを除いた3行目のキー 2 が前の行のキー 3 より小さい値です", err.to_string());
+        assert!(merge("1,東京都\n", "2,200\n1,100\n", JoinType::Left).is_err());
+    }
+
+    #[test]
+    fn test_semi_join() {
+        // 複合キーで絞り込む。ハッシュ結合は入力の順序を問わない
+        let cases = [
+            (Algorithm::Hash, "1,a,100\n2,b,200\n1,b,300\n3,a,400\n", "b,1\na,3\nb,1\n"),
+            (Algorithm::Merge, "1,a,100\n1,b,300\n2,b,200\n3,a,400\n", "b,1\nb,1\na,3\n"),
+        ];
+        for (algorithm, left, right) in &cases {
+            let mut cfg = Config::new("id,name,price", "name,id", b',', "id,name", "id,name", false);
+            cfg.algorithm = *algorithm;
+            cfg.join_type = JoinType::Semi;
+            let mut writer = Vec::new();
+            cfg.write_header(&mut writer).unwrap();
+            mjoin(&mut left.as_bytes(), &mut right.as_bytes(), &mut writer, &cfg).unwrap();
+            assert_eq!("id,name,price\n1,b,300\n3,a,400\n", String::from_utf8(writer).unwrap());
+            cfg.join_type = JoinType::Anti;
+            let mut writer = Vec::new();
+            mjoin(&mut left.as_bytes(), &mut right.as_bytes(), &mut writer, &cfg).unwrap();
+            assert_eq!("1,a,100\n2,b,200\n", String::from_utf8(writer).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_set_fields() {
+        let mut cfg = config(JoinType::Left, Side::Right);
+        cfg.set_fields("R.price,L.0..1,price,src:master", false);
+        let expected = vec![
+            (b"price".to_vec(), Output::Column(Side::Right, 1)),
+            (b"id".to_vec(), Output::Column(Side::Left, 0)),
+            (b"title".to_vec(), Output::Column(Side::Left, 1)),
+            (b"price".to_vec(), Output::Column(Side::Right, 1)),
+            (b"src".to_vec(), Output::Constant(b"master".to_vec())),
+        ];
+        assert_eq!(expected, cfg.columns);
+        assert_eq!("price,title\n100,東京都\n200,大阪府\n250,大阪府\n,茨城県\n200,おおさか\n250,おおさか\n", {
+            cfg.set_fields("R.price,title", false);
+            join(&cfg)
+        });
+    }
+}
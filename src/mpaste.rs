@@ -0,0 +1,185 @@
+//! 複数のファイルを行ごとに横に連結する
+use crate::mcut;
+use crate::reader::{Reader, Record};
+use crate::util;
+use std::collections::HashMap;
+use std::io;
+use std::io::prelude::*;
+
+/// ファイルの行数が異なる場合の動作
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OnMismatch {
+    /// エラーにする
+    Error,
+    /// 行数の少ないファイルのカラムを空文字列で埋める
+    Pad,
+}
+
+/// 連結の設定
+pub struct Config {
+    pub delimiter: u8,
+    /// 出力するカラム名
+    pub columns: Vec<String>,
+    /// ファイルごとのヘッダのカラム数
+    pub widths: Vec<usize>,
+    /// ファイルごとの接頭辞。カラム名の重複を避けるためとエラーメッセージに使用する
+    pub prefixes: Vec<String>,
+    pub on_mismatch: OnMismatch,
+    /// この文字列で始まる行はコメント行とみなし、出力しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// 全てのファイルのヘッダ行から出力するカラム名を決めてConfigを生成する
+    ///
+    /// 複数のファイルに存在するカラム名は「接頭辞.カラム名」に変更する。
+    ///
+    /// # Arguments
+    /// * `first_lines` - 全てのファイルのヘッダ行
+    /// * `prefixes`    - ファイルごとの接頭辞
+    /// * `delimiter`   - 区切り文字
+    pub fn new(first_lines: &[&str], prefixes: &[&str], delimiter: u8) -> Self {
+        let headers: Vec<Vec<&str>> = first_lines.iter().map(|first_line| mcut::Config::split_header(first_line, delimiter)).collect();
+        // カラム名ごとに、そのカラム名が存在するファイルの数を数える
+        let mut files: HashMap<&str, usize> = HashMap::new();
+        for header in &headers {
+            let mut names = header.clone();
+            names.sort();
+            names.dedup();
+            for name in names {
+                *files.entry(name).or_insert(0) += 1;
+            }
+        }
+        let mut columns: Vec<String> = Vec::new();
+        for (header, prefix) in headers.iter().zip(prefixes) {
+            for name in header {
+                if files[name] > 1 {
+                    let prefixed = format!("{}.{}", prefix, name);
+                    if columns.contains(&prefixed) || files.contains_key(prefixed.as_str()) {
+                        panic!("接頭辞を付けてもカラム名が重複します: {} (ファイルごとに異なる接頭辞を指定してください)", prefixed);
+                    }
+                    columns.push(prefixed);
+                } else {
+                    columns.push(name.to_string());
+                }
+            }
+        }
+        Config {
+            delimiter,
+            columns,
+            widths: headers.iter().map(|header| header.len()).collect(),
+            prefixes: prefixes.iter().map(|prefix| prefix.to_string()).collect(),
+            on_mismatch: OnMismatch::Error,
+            comment_prefix: None,
+        }
+    }
+
+    /// 出力するカラム名をヘッダとして出力する
+    ///
+    /// # Arguments
+    /// * `writer` - ヘッダ行を書き込むwriter
+    pub fn write_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "{}", util::join(self.delimiter as char, &self.columns))
+    }
+
+    /// 行をファイルのヘッダのカラム数に揃えて出力する
+    ///
+    /// カラム数がヘッダより少ない行は空文字列で埋め、多い行はヘッダのカラム数を超えるカラムを出力しない。
+    /// 行がない(None)場合は全てのカラムを空文字列で埋める。
+    fn write_fields<W: Write>(&self, writer: &mut W, record: Option<&Record>, width: usize) -> io::Result<()> {
+        let (line, count) = match record {
+            Some(record) if record.len() > width => {
+                let (_, end) = record.span(width - 1).unwrap();
+                return writer.write_all(&record.as_bytes()[..end]);
+            }
+            Some(record) => (record.as_bytes(), record.len()),
+            None         => (&b""[..], 1),
+        };
+        writer.write_all(line)?;
+        for _ in count..width {
+            writer.write_all(&[self.delimiter])?;
+        }
+        Ok(())
+    }
+}
+
+/// 各readerから1行ずつ読み込み、区切り文字で連結してwriterに書き込む
+///
+/// カラム数がヘッダより少ない行は空文字列で埋め、多い行はヘッダのカラム数を超えるカラムを出力しない。
+/// 行数の少ないファイルがある場合は cfg.on_mismatch に従ってエラーにするか、空文字列で埋める。
+///
+/// # Arguments
+/// * `readers` - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `cfg`     - カラム数や行数が異なる場合の設定
+pub fn mpaste<R: BufRead, W: Write>(readers: &mut [R], writer: &mut W, cfg: &Config) -> io::Result<()> {
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let mut readers: Vec<Reader<&mut R>> = readers.iter_mut()
+        .map(|reader| Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix))
+        .collect();
+    let mut row_count = 0;
+    loop {
+        let mut lines = Vec::with_capacity(readers.len());
+        for reader in readers.iter_mut() {
+            lines.push(reader.read_record()?);
+        }
+        if lines.iter().all(|line| line.is_none()) {
+            return Ok(());
+        }
+        if cfg.on_mismatch == OnMismatch::Error {
+            if let Some(i) = lines.iter().position(|line| line.is_none()) {
+                let message = format!("{} の行数が他のファイルより少ないです: ヘッダを除いて{}行です", cfg.prefixes[i], row_count);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+        }
+        row_count += 1;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(&[cfg.delimiter])?;
+            }
+            cfg.write_fields(writer, line.as_ref(), cfg.widths[i])?;
+        }
+        writer.write_all(b"\n")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let cfg = Config::new(&["id,title", "id,score", "rank"], &["a", "b", "c"], b',');
+        assert_eq!(vec!["a.id", "title", "b.id", "score", "rank"], cfg.columns);
+        assert_eq!(vec![2, 2, 1], cfg.widths);
+    }
+
+    #[test]
+    #[should_panic(expected = "接頭辞を付けてもカラム名が重複します: x.id")]
+    fn test_new_duplicate_prefix() {
+        Config::new(&["id", "id"], &["x", "x"], b',');
+    }
+
+    #[test]
+    fn test_mpaste() {
+        let mut cfg = Config::new(&["id,title", "score", "rank,memo"], &["a", "b", "c"], b',');
+        let readers = || vec!["1,a\n2,b\n3\n".as_bytes(), "10\n20\n".as_bytes(), "1,x\n2\n3,z\n".as_bytes()];
+        let err = mpaste(&mut readers(), &mut Vec::new(), &cfg).unwrap_err();
+        assert_eq!("b の行数が他のファイルより少ないです: ヘッダを除いて2行です", err.to_string());
+        cfg.on_mismatch = OnMismatch::Pad;
+        let mut writer = Vec::new();
+        cfg.write_header(&mut writer).unwrap();
+        mpaste(&mut readers(), &mut writer, &cfg).unwrap();
+        assert_eq!("id,title,score,rank,memo\n1,a,10,1,x\n2,b,20,2,\n3,,,3,z\n", String::from_utf8(writer).unwrap());
+    }
+
+    #[test]
+    fn test_mpaste_extra_fields() {
+        // ヘッダより多いカラムは出力せず、後のファイルのカラムがずれないようにする
+        let cfg = Config::new(&["c", "a,b"], &["x", "y"], b',');
+        let mut writer = Vec::new();
+        cfg.write_header(&mut writer).unwrap();
+        mpaste(&mut ["3,9\n4\n".as_bytes(), "1,2\n5,6,7\n".as_bytes()], &mut writer, &cfg).unwrap();
+        assert_eq!("c,a,b\n3,1,2\n4,5,6\n", String::from_utf8(writer).unwrap());
+    }
+}
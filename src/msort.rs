@@ -0,0 +1,354 @@
+//! ヘッダのカラム名でソートキーを指定する外部マージソート
+use crate::mcut;
+use crate::reader::{Reader, Record};
+use crate::util;
+use crate::util::TempDir;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::mem;
+use std::path::PathBuf;
+
+/// 一度にマージする一時ファイルの最大数。これより多い場合は複数回に分けてマージする
+const MERGE_FAN_IN: usize = 64;
+
+/// ソートキーの比較方法
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum KeyType {
+    /// バイト列として比較する(ロケールに依存しない)
+    Bytes,
+    /// 数値として比較する。数値として解釈できない値は数値より先とする
+    Numeric,
+    /// 数字の並びを数値として比較する自然順
+    Natural,
+}
+
+/// ソートキー
+#[derive(Debug, PartialEq)]
+pub struct SortKey {
+    /// 0から始まるカラム番号
+    pub idx: usize,
+    pub key_type: KeyType,
+    /// trueの場合は降順
+    pub descending: bool,
+}
+
+/// ソートの設定
+pub struct Config {
+    pub delimiter: u8,
+    pub keys: Vec<SortKey>,
+    /// メモリ上でソートする行の合計バイト数の上限。これを超えた場合はソート済みの行を一時ファイルに書き出す
+    pub buffer_size: usize,
+    /// 一時ファイルを作成するディレクトリ
+    pub temp_dir: PathBuf,
+    /// この文字列で始まる行はコメント行とみなし、出力しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// ヘッダ行とソートキーの指定からConfigを生成する
+    ///
+    /// ソートキーは「カラム名[:型][:順序]」をカンマ区切りで指定する。
+    /// 型は s(バイト列, デフォルト), n(数値), v(自然順)、順序は asc(昇順, デフォルト), desc(降順) のいずれか。
+    ///
+    /// # Arguments
+    /// * `first_line`  - ヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `keys`        - ソートキーの指定。例) price:n:desc,title
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn parse_keys(first_line: &str, delimiter: u8, keys: &str, ignore_case: bool) -> Self {
+        let header = mcut::Config::split_header(first_line, delimiter);
+        let keys = keys.split(',').map(|spec| Self::parse_key(spec, &header, ignore_case)).collect();
+        Config {
+            delimiter,
+            keys,
+            buffer_size: 64 * 1024 * 1024,
+            temp_dir: env::temp_dir(),
+            comment_prefix: None,
+        }
+    }
+
+    fn parse_key(spec: &str, header: &[&str], ignore_case: bool) -> SortKey {
+        let mut parts = spec.split(':');
+        let col_name = parts.next().unwrap();
+        let idx = mcut::Config::find_column(col_name, header, ignore_case).unwrap_or_else(|| {
+            panic!("不明なフィールド: {}{}", col_name, mcut::Config::unknown_field_hint(col_name, header));
+        });
+        let mut key = SortKey { idx, key_type: KeyType::Bytes, descending: false };
+        for part in parts {
+            match part {
+                "s"    => key.key_type = KeyType::Bytes,
+                "n"    => key.key_type = KeyType::Numeric,
+                "v"    => key.key_type = KeyType::Natural,
+                "asc"  => key.descending = false,
+                "desc" => key.descending = true,
+                _ => panic!("不明なソートキーの指定: {} (s, n, v, asc, desc のいずれかを指定してください)", spec),
+            }
+        }
+        key
+    }
+
+    /// 2つの行をソートキーに従って比較する
+    fn compare(&self, a: &Row, b: &Row) -> Ordering {
+        for (i, key) in self.keys.iter().enumerate() {
+            let (field_a, field_b) = (a.field(i), b.field(i));
+            let ord = match key.key_type {
+                KeyType::Bytes   => field_a.cmp(field_b),
+                KeyType::Natural => util::natural_cmp(field_a, field_b),
+                KeyType::Numeric => match (a.numbers[i], b.numbers[i]) {
+                    (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                    (Some(_), None)    => Ordering::Greater,
+                    (None, Some(_))    => Ordering::Less,
+                    (None, None)       => field_a.cmp(field_b),
+                },
+            };
+            let ord = if key.descending { ord.reverse() } else { ord };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+/// ソート対象の1行
+struct Row {
+    /// 改行を除いた行
+    line: Vec<u8>,
+    /// ソートキーごとのカラムの(開始位置, 終了位置)
+    ranges: Vec<(usize, usize)>,
+    /// ソートキーごとのカラムを数値として解釈した値
+    numbers: Vec<Option<f64>>,
+}
+
+impl Row {
+    fn new(record: &Record, cfg: &Config) -> Self {
+        let line = record.as_bytes().to_vec();
+        // カラムが足りない行は空文字列とみなす
+        let ranges: Vec<(usize, usize)> = cfg.keys.iter()
+            .map(|key| record.span(key.idx).unwrap_or((line.len(), line.len())))
+            .collect();
+        let numbers = cfg.keys.iter().zip(&ranges).map(|(key, (start, end))| {
+            if key.key_type != KeyType::Numeric {
+                return None;
+            }
+            std::str::from_utf8(&line[*start..*end]).ok()
+                .and_then(|s| s.trim().parse::<f64>().ok())
+                .filter(|n| !n.is_nan())
+        }).collect();
+        Row { line, ranges, numbers }
+    }
+
+    fn field(&self, i: usize) -> &[u8] {
+        let (start, end) = self.ranges[i];
+        &self.line[start..end]
+    }
+
+    /// メモリ上で保持するのに必要なおおよそのバイト数
+    fn size(&self) -> usize {
+        mem::size_of::<Row>() + self.line.capacity()
+            + self.ranges.len() * (mem::size_of::<(usize, usize)>() + mem::size_of::<Option<f64>>())
+    }
+}
+
+/// readerから読み込んだ行をcfgのソートキーに従って安定ソートし、writerに書き込む
+///
+/// メモリ上の行の合計バイト数が cfg.buffer_size を超えた場合は、ソート済みの行を一時ファイルに書き出して最後にマージする。
+///
+/// # Arguments
+/// * `reader` - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `cfg`    - ソートキーや一時ファイルの設定
+pub fn msort<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, cfg: &Config) -> io::Result<()> {
+    let mut temp_dir: Option<TempDir> = None;
+    let mut runs: Vec<PathBuf> = Vec::new();
+    let mut rows: Vec<Row> = Vec::new();
+    let mut size = 0;
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]));
+    while let Some(record) = reader.read_record()? {
+        let row = Row::new(&record, cfg);
+        size += row.size();
+        rows.push(row);
+        if size >= cfg.buffer_size {
+            let dir = match temp_dir {
+                Some(ref dir) => dir,
+                None          => temp_dir.get_or_insert(TempDir::new(&cfg.temp_dir, "sort")?),
+            };
+            rows.sort_by(|a, b| cfg.compare(a, b));
+            runs.push(write_run(dir, runs.len(), &rows)?);
+            rows.clear();
+            size = 0;
+        }
+    }
+    rows.sort_by(|a, b| cfg.compare(a, b));
+    let dir = match temp_dir {
+        Some(ref dir) => dir,
+        None => {
+            // 一時ファイルに書き出していなければそのまま出力する
+            for row in &rows {
+                writer.write_all(&row.line)?;
+                writer.write_all(b"\n")?;
+            }
+            return Ok(());
+        }
+    };
+    if !rows.is_empty() {
+        runs.push(write_run(dir, runs.len(), &rows)?);
+        rows.clear();
+    }
+    // 一度にマージできない場合は、ファイルの順番を保ったまま隣接するファイルをまとめてマージする(安定性のため)
+    let mut seq = runs.len();
+    while runs.len() > MERGE_FAN_IN {
+        let mut merged = Vec::new();
+        for group in runs.chunks(MERGE_FAN_IN) {
+            let path = dir.path.join(format!("run-{}", seq));
+            seq += 1;
+            let mut run_writer = BufWriter::new(File::create(&path)?);
+            merge(group, &mut run_writer, cfg)?;
+            run_writer.flush()?;
+            for run in group {
+                fs::remove_file(run)?;
+            }
+            merged.push(path);
+        }
+        runs = merged;
+    }
+    merge(&runs, writer, cfg)
+}
+
+/// ソート済みの行を一時ファイルに書き出す
+fn write_run(dir: &TempDir, seq: usize, rows: &[Row]) -> io::Result<PathBuf> {
+    let path = dir.path.join(format!("run-{}", seq));
+    let mut writer = BufWriter::new(File::create(&path)?);
+    for row in rows {
+        writer.write_all(&row.line)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+/// ソート済みの一時ファイルをk-wayマージしてwriterに書き込む
+///
+/// ソートキーが等しい行は先に指定したファイルの行を先に出力する。
+fn merge<W: Write>(runs: &[PathBuf], writer: &mut W, cfg: &Config) -> io::Result<()> {
+    // 一時ファイルにはコメント行を書き出していないので、コメント行の設定は使わない
+    let mut readers: Vec<Reader<BufReader<File>>> = Vec::with_capacity(runs.len());
+    let mut heap: BinaryHeap<Entry> = BinaryHeap::with_capacity(runs.len());
+    for (run, path) in runs.iter().enumerate() {
+        let mut reader = Reader::new(BufReader::new(File::open(path)?), cfg.delimiter);
+        if let Some(record) = reader.read_record()? {
+            heap.push(Entry { row: Row::new(&record, cfg), run, cfg });
+        }
+        readers.push(reader);
+    }
+    while let Some(entry) = heap.pop() {
+        writer.write_all(&entry.row.line)?;
+        writer.write_all(b"\n")?;
+        if let Some(record) = readers[entry.run].read_record()? {
+            heap.push(Entry { row: Row::new(&record, cfg), run: entry.run, cfg });
+        }
+    }
+    Ok(())
+}
+
+/// マージ時にヒープに格納する行
+struct Entry<'a> {
+    row: Row,
+    /// 行を読み込んだ一時ファイルの番号
+    run: usize,
+    cfg: &'a Config,
+}
+
+impl<'a> Ord for Entry<'a> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeapは最大値から取り出すので逆順にする
+        self.cfg.compare(&self.row, &other.row).then_with(|| self.run.cmp(&other.run)).reverse()
+    }
+}
+
+impl<'a> PartialOrd for Entry<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> PartialEq for Entry<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a> Eq for Entry<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort(data: &str, keys: &str, buffer_size: usize) -> String {
+        let mut reader = data.as_bytes();
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = Config::parse_keys(first_line.trim_end(), b',', keys, false);
+        cfg.buffer_size = buffer_size;
+        let mut writer = Vec::new();
+        msort(&mut reader, &mut writer, &cfg).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        let cfg = Config::parse_keys("id,title,price", b',', "price:n:desc,title,0:v", false);
+        let expected = vec![
+            SortKey { idx: 2, key_type: KeyType::Numeric, descending: true },
+            SortKey { idx: 1, key_type: KeyType::Bytes, descending: false },
+            SortKey { idx: 0, key_type: KeyType::Natural, descending: false },
+        ];
+        assert_eq!(expected, cfg.keys);
+    }
+
+    #[test]
+    #[should_panic(expected = "不明なソートキーの指定: price:x")]
+    fn test_parse_keys_invalid() {
+        Config::parse_keys("id,title,price", b',', "price:x", false);
+    }
+
+    #[test]
+    fn test_msort_1() {
+        let data = "id,name,price\n1,b,10\n2,a,9.5\n3,c,abc\n4,a,100\n5,b,10\n";
+        assert_eq!("3,c,abc\n2,a,9.5\n1,b,10\n5,b,10\n4,a,100\n", sort(data, "price:n", 1 << 20));
+        assert_eq!("4,a,100\n1,b,10\n5,b,10\n2,a,9.5\n3,c,abc\n", sort(data, "price:n:desc", 1 << 20));
+        // 安定ソート
+        assert_eq!("2,a,9.5\n4,a,100\n1,b,10\n5,b,10\n3,c,abc\n", sort(data, "name", 1 << 20));
+        assert_eq!("4,a,100\n2,a,9.5\n5,b,10\n1,b,10\n3,c,abc\n", sort(data, "name,id:n:desc", 1 << 20));
+    }
+
+    #[test]
+    fn test_msort_2() {
+        // 自然順、カラムが足りない行
+        let data = "file\nfile10\nfile2\n\nfile1\n";
+        assert_eq!("\nfile1\nfile2\nfile10\n", sort(data, "file:v", 1 << 20));
+        assert_eq!("\nfile1\nfile10\nfile2\n", sort(data, "file", 1 << 20));
+    }
+
+    #[test]
+    fn test_msort_external() {
+        // 一時ファイルへの書き出しと複数回のマージ
+        let mut data = String::from("id,key\n");
+        let mut expected: Vec<(usize, usize)> = Vec::new();
+        for i in 0..1000 {
+            let key = (i * 7919) % 101;
+            data.push_str(&format!("{},{}\n", i, key));
+            expected.push((key, i));
+        }
+        expected.sort();
+        let expected: String = expected.iter().map(|(key, i)| format!("{},{}\n", i, key)).collect();
+        assert_eq!(expected, sort(&data, "key:n", 1));
+        assert_eq!(expected, sort(&data, "key:n", 2000));
+    }
+}
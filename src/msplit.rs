@@ -0,0 +1,281 @@
+//! ファイルをカラムの値や行数、バイト数ごとに複数のファイルに分割する
+use crate::mcut;
+use crate::reader::Reader;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// 分割の方法
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    /// 指定したカラム(0から始まるカラム番号)の値ごとに分割する
+    Key(Vec<usize>),
+    /// 指定した行数ごとに分割する
+    Rows(usize),
+    /// ヘッダを含めて指定したバイト数以下になるように分割する
+    Bytes(usize),
+}
+
+impl Mode {
+    /// ヘッダ行とカラム名の指定から、カラムの値ごとに分割するModeを生成する
+    ///
+    /// # Arguments
+    /// * `first_line`  - ヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `keys`        - 分割するカラム名のカンマ区切り
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn keys(first_line: &str, delimiter: u8, keys: &str, ignore_case: bool) -> Self {
+        let header = mcut::Config::split_header(first_line, delimiter);
+        Mode::Key(keys.split(',').map(|col_name| {
+            mcut::Config::find_column(col_name, &header, ignore_case).unwrap_or_else(|| {
+                panic!("不明なフィールド: {}{}", col_name, mcut::Config::unknown_field_hint(col_name, &header));
+            })
+        }).collect())
+    }
+}
+
+/// 分割の設定
+pub struct Config {
+    pub delimiter: u8,
+    pub mode: Mode,
+    /// 出力ファイル名のテンプレート。「{}」をカラムの値もしくは0001から始まる連番に置き換える
+    pub template: String,
+    /// 同時に開いておく出力ファイルの最大数。超えた場合は最も長く書き込んでいないファイルを閉じる
+    pub max_open_files: usize,
+    /// この文字列で始まる行はコメント行とみなし、出力しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// # Arguments
+    /// * `delimiter` - 区切り文字
+    /// * `mode`      - 分割の方法
+    /// * `template`  - 出力ファイル名のテンプレート。例) out/{}.csv
+    pub fn new(delimiter: u8, mode: Mode, template: &str) -> Self {
+        Config { delimiter, mode, template: template.to_string(), max_open_files: 64, comment_prefix: None }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        PathBuf::from(self.template.replace("{}", name))
+    }
+}
+
+/// カラムの値をファイル名に使える文字列に変換する
+///
+/// パス区切り文字と制御文字は「_」に置き換え、空文字列と「.」「..」は「_」とする。
+fn file_name(value: &[u8]) -> String {
+    let name: String = String::from_utf8_lossy(value).chars()
+        .map(|c| if c == '/' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    if name.is_empty() || name == "." || name == ".." { String::from("_") } else { name }
+}
+
+/// 出力ファイル。開いているファイルの数が上限を超えた場合は最も長く書き込んでいないファイルを閉じる(LRU)
+struct Outputs<'a> {
+    header: &'a [u8],
+    max_open_files: usize,
+    /// 開いているファイルと最後に書き込んだ時刻
+    open: HashMap<PathBuf, (BufWriter<File>, u64)>,
+    /// 作成したファイルの一覧(作成順)
+    created: Vec<PathBuf>,
+    /// 書き込みのたびに増やす時刻
+    clock: u64,
+}
+
+impl<'a> Outputs<'a> {
+    /// pathのファイルに1行書き込む。初めて書き込む場合はファイルを作成してヘッダを書き込み、閉じたファイルの場合は追記する
+    fn write(&mut self, path: &Path, line: &[u8], created: bool) -> io::Result<()> {
+        self.clock += 1;
+        if !self.open.contains_key(path) {
+            if self.open.len() >= self.max_open_files {
+                let oldest = self.open.iter().min_by_key(|(_, (_, used))| *used).map(|(path, _)| path.clone()).unwrap();
+                let (mut writer, _) = self.open.remove(&oldest).unwrap();
+                writer.flush()?;
+            }
+            let writer = if created {
+                BufWriter::new(OpenOptions::new().append(true).open(path)?)
+            } else {
+                if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut writer = BufWriter::new(File::create(path)?);
+                writer.write_all(self.header)?;
+                writer.write_all(b"\n")?;
+                self.created.push(path.to_path_buf());
+                writer
+            };
+            self.open.insert(path.to_path_buf(), (writer, self.clock));
+        }
+        let (writer, used) = self.open.get_mut(path).unwrap();
+        *used = self.clock;
+        writer.write_all(line)?;
+        writer.write_all(b"\n")
+    }
+
+    fn close(self) -> io::Result<Vec<PathBuf>> {
+        for (_, (mut writer, _)) in self.open {
+            writer.flush()?;
+        }
+        Ok(self.created)
+    }
+}
+
+/// readerから読み込んだ行をcfgの分割の方法に従って複数のファイルに書き込み、作成したファイルの一覧を返す
+///
+/// 全ての出力ファイルの先頭にヘッダ行を書き込む。既に存在するファイルは上書きする。
+///
+/// # Arguments
+/// * `reader`     - ヘッダ行を読み込んだ後のreader
+/// * `first_line` - ヘッダ行
+/// * `cfg`        - 分割の方法や出力ファイル名の設定
+pub fn msplit<R: BufRead>(reader: &mut R, first_line: &str, cfg: &Config) -> io::Result<Vec<PathBuf>> {
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let mut outputs = Outputs {
+        header: first_line.as_bytes(),
+        max_open_files: cfg.max_open_files,
+        open: HashMap::new(),
+        created: Vec::new(),
+        clock: 0,
+    };
+    // Key: カラムの値から出力ファイルへの索引と、出力ファイルからカラムの値への索引(ファイル名の重複の検出用)
+    let mut paths: HashMap<Vec<u8>, PathBuf> = HashMap::new();
+    let mut keys: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+    // Rows, Bytes: 出力中のファイルの連番と、そのファイルの行数およびバイト数
+    let (mut chunk, mut rows, mut bytes) = (0, 0, 0);
+    let mut path = PathBuf::new();
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        let line = record.as_bytes();
+        let created = match cfg.mode {
+            Mode::Key(ref columns) => {
+                // カラムが足りない行は空文字列とみなす
+                let values: Vec<&[u8]> = columns.iter().map(|idx| record.field(*idx)).collect();
+                let key = values.join(&cfg.delimiter);
+                match paths.get(&key) {
+                    Some(known) => {
+                        path.clone_from(known);
+                        true
+                    }
+                    None => {
+                        let names: Vec<String> = values.iter().map(|value| file_name(value)).collect();
+                        path = cfg.path(&names.join("_"));
+                        if let Some(other) = keys.get(&path) {
+                            let message = format!(
+                                "カラムの値 {} と {} の出力ファイル名が同じです: {}",
+                                String::from_utf8_lossy(other), String::from_utf8_lossy(&key), path.display()
+                            );
+                            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                        }
+                        keys.insert(path.clone(), key.clone());
+                        paths.insert(key, path.clone());
+                        false
+                    }
+                }
+            }
+            Mode::Rows(_) | Mode::Bytes(_) => {
+                let full = match cfg.mode {
+                    Mode::Rows(max_rows)   => rows >= max_rows,
+                    Mode::Bytes(max_bytes) => rows > 0 && bytes + line.len() + 1 > max_bytes,
+                    Mode::Key(_)           => unreachable!(),
+                };
+                if chunk == 0 || full {
+                    chunk += 1;
+                    rows = 0;
+                    bytes = first_line.len() + 1;
+                    path = cfg.path(&format!("{:04}", chunk));
+                    // 前のファイルには書き込まないので閉じる
+                    let previous: Vec<PathBuf> = outputs.open.keys().cloned().collect();
+                    for previous in previous {
+                        let (mut writer, _) = outputs.open.remove(&previous).unwrap();
+                        writer.flush()?;
+                    }
+                }
+                rows += 1;
+                bytes += line.len() + 1;
+                rows > 1
+            }
+        };
+        outputs.write(&path, line, created)?;
+    }
+    outputs.close()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+    use std::env;
+
+    fn split(data: &str, mode: Mode, max_open_files: usize) -> Vec<(String, String)> {
+        let dir = TempDir::new(&env::temp_dir(), "split-test").unwrap();
+        let mut reader = data.as_bytes();
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = Config::new(b',', mode, &format!("{}/out/{{}}.csv", dir.path.display()));
+        cfg.max_open_files = max_open_files;
+        let paths = msplit(&mut reader, first_line.trim_end(), &cfg).unwrap();
+        paths.iter().map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read_to_string(path).unwrap())
+        }).collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected.iter().map(|(name, content)| (name.to_string(), content.to_string())).collect()
+    }
+
+    #[test]
+    fn test_msplit_key() {
+        let data = "id,area,pref\n1,関東,東京\n2,関西,大阪\n3,関東,千葉\n4,関西,大阪\n5,../x\n";
+        let expected = pairs(&[
+            ("関東.csv", "id,area,pref\n1,関東,東京\n3,関東,千葉\n"),
+            ("関西.csv", "id,area,pref\n2,関西,大阪\n4,関西,大阪\n"),
+            (".._x.csv", "id,area,pref\n5,../x\n"),
+        ]);
+        // 開いておくファイル数が1の場合も、閉じたファイルに追記する
+        for max_open_files in &[1, 64] {
+            assert_eq!(expected, split(data, Mode::keys("id,area,pref", b',', "area", false), *max_open_files));
+        }
+        let expected = pairs(&[
+            ("関東_東京.csv", "id,area,pref\n1,関東,東京\n"),
+            ("関西_大阪.csv", "id,area,pref\n2,関西,大阪\n4,関西,大阪\n"),
+            ("関東_千葉.csv", "id,area,pref\n3,関東,千葉\n"),
+            (".._x__.csv", "id,area,pref\n5,../x\n"),
+        ]);
+        assert_eq!(expected, split(data, Mode::keys("id,area,pref", b',', "area,pref", false), 2));
+    }
+
+    #[test]
+    fn test_msplit_key_conflict() {
+        let dir = TempDir::new(&env::temp_dir(), "split-test").unwrap();
+        let cfg = Config::new(b',', Mode::Key(vec![1]), &format!("{}/{{}}.csv", dir.path.display()));
+        let err = msplit(&mut "1,a/b\n2,a_b\n".as_bytes(), "id,name", &cfg).unwrap_err();
+        assert!(err.to_string().starts_with("カラムの値 a/b と a_b の出力ファイル名が同じです"));
+    }
+
+    #[test]
+    fn test_msplit_chunk() {
+        let data = "id,name\n1,a\n2,bb\n3,c\n4,dd\n5,e\n";
+        let expected = pairs(&[
+            ("0001.csv", "id,name\n1,a\n2,bb\n"),
+            ("0002.csv", "id,name\n3,c\n4,dd\n"),
+            ("0003.csv", "id,name\n5,e\n"),
+        ]);
+        assert_eq!(expected, split(data, Mode::Rows(2), 64));
+        // ヘッダを含めて12バイト以下にする。1行で超える場合はその行のみ出力する
+        let expected = pairs(&[
+            ("0001.csv", "id,name\n1,a\n"),
+            ("0002.csv", "id,name\n2,bb\n"),
+            ("0003.csv", "id,name\n3,c\n"),
+            ("0004.csv", "id,name\n4,dd\n"),
+            ("0005.csv", "id,name\n5,e\n"),
+        ]);
+        assert_eq!(expected, split(data, Mode::Bytes(12), 64));
+        let expected = pairs(&[("0001.csv", "id,name\n1,a\n2,bb\n3,c\n"), ("0002.csv", "id,name\n4,dd\n5,e\n")]);
+        assert_eq!(expected, split(data, Mode::Bytes(24), 64));
+    }
+}
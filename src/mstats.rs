@@ -0,0 +1,472 @@
+//! ファイルのカラムごとの型や値の分布を集計する
+use crate::mcount::SpaceSaving;
+use crate::mcut;
+use crate::reader::Reader;
+use crate::util;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::io;
+use std::io::prelude::*;
+
+/// 分位数を求めるためにカラムごとに保持する数値の標本数
+const RESERVOIR_SIZE: usize = 10000;
+
+/// 頻出値を求めるためにカラムごとにメモリ上で数える値の種類の上限
+const TOP_CAPACITY: usize = 1000;
+
+/// HyperLogLog のレジスタ数(2^HLL_PRECISION)を決める精度
+const HLL_PRECISION: u32 = 14;
+
+/// 出力する分位数
+const QUANTILES: &[(&str, f64)] = &[("p25", 0.25), ("p50", 0.5), ("p75", 0.75)];
+
+/// 集計結果の出力形式
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// 1カラム1行の区切り文字で区切られた表
+    Table,
+    /// カラムごとのオブジェクトの配列
+    Json,
+}
+
+/// カラムの値から推定した型
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ValueType {
+    Int,
+    Float,
+    /// YYYY-MM-DD もしくは YYYY/MM/DD で始まる日付(日時)
+    Date,
+    String,
+}
+
+impl ValueType {
+    /// 1つの値の型を推定する
+    ///
+    /// # Arguments
+    /// * `value` - 空文字列、null以外の値
+    pub fn infer(value: &[u8]) -> Self {
+        let s = match std::str::from_utf8(value) {
+            Ok(s)  => s.trim(),
+            Err(_) => return ValueType::String,
+        };
+        if s.parse::<i64>().is_ok() {
+            ValueType::Int
+        } else if s.parse::<f64>().ok().map_or(false, |n| n.is_finite()) {
+            ValueType::Float
+        } else if is_date(s.as_bytes()) {
+            ValueType::Date
+        } else {
+            ValueType::String
+        }
+    }
+
+    /// 2つの型の両方の値を表せる型を返す
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ValueType::Int, ValueType::Float) | (ValueType::Float, ValueType::Int) => ValueType::Float,
+            _ => ValueType::String,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ValueType::Int    => "int",
+            ValueType::Float  => "float",
+            ValueType::Date   => "date",
+            ValueType::String => "string",
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        self == ValueType::Int || self == ValueType::Float
+    }
+}
+
+/// YYYY-MM-DD もしくは YYYY/MM/DD で始まり、日付の後ろが空か「 」「T」で始まる時刻かどうかを返す
+fn is_date(s: &[u8]) -> bool {
+    let digits = |range: std::ops::Range<usize>| s[range].iter().all(|c| c.is_ascii_digit());
+    if s.len() < 10 || !digits(0..4) || !digits(5..7) || !digits(8..10) {
+        return false;
+    }
+    if (s[4] != b'-' && s[4] != b'/') || s[7] != s[4] {
+        return false;
+    }
+    let month = (s[5] - b'0') * 10 + (s[6] - b'0');
+    let day = (s[8] - b'0') * 10 + (s[9] - b'0');
+    if month < 1 || month > 12 || day < 1 || day > 31 {
+        return false;
+    }
+    s.len() == 10 || (s.len() >= 16 && (s[10] == b' ' || s[10] == b'T') && digits(11..13) && s[13] == b':' && digits(14..16))
+}
+
+/// 空文字列もしくは null, NULL, \N をnullとみなす
+fn is_null(value: &[u8]) -> bool {
+    value.is_empty() || value == b"null" || value == b"NULL" || value == b"\\N"
+}
+
+/// 集計の設定
+pub struct Config {
+    pub delimiter: u8,
+    /// 集計するカラムの0から始まるカラム番号
+    pub columns: Vec<usize>,
+    /// 集計するカラムのカラム名
+    pub names: Vec<String>,
+    /// trueの場合、重複を除いた値の数を HyperLogLog で推定する
+    pub approx_distinct: bool,
+    /// 出力する頻出値の件数
+    pub top: usize,
+    pub format: Format,
+    /// この文字列で始まる行はコメント行とみなし、集計しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// ヘッダ行と集計するカラムの指定からConfigを生成する
+    ///
+    /// # Arguments
+    /// * `first_line`  - ヘッダ行
+    /// * `delimiter`   - 区切り文字
+    /// * `fields`      - 集計するカラム名のカンマ区切り。Noneの場合は全てのカラムを集計する
+    /// * `ignore_case` - trueの場合、大文字小文字と前後の空白を無視してカラム名を比較する
+    pub fn parse_fields(first_line: &str, delimiter: u8, fields: Option<&str>, ignore_case: bool) -> Self {
+        let header = mcut::Config::split_header(first_line, delimiter);
+        let columns: Vec<usize> = match fields {
+            Some(fields) => fields.split(',').map(|col_name| {
+                mcut::Config::find_column(col_name, &header, ignore_case).unwrap_or_else(|| {
+                    panic!("不明なフィールド: {}{}", col_name, mcut::Config::unknown_field_hint(col_name, &header));
+                })
+            }).collect(),
+            None => (0..header.len()).collect(),
+        };
+        let names = columns.iter().map(|idx| header[*idx].to_string()).collect();
+        Config { delimiter, columns, names, approx_distinct: false, top: 3, format: Format::Table, comment_prefix: None }
+    }
+}
+
+/// 重複を除いた値の数
+enum Distinct {
+    Exact(HashSet<Vec<u8>>),
+    Approx(HyperLogLog),
+}
+
+/// 重複を除いた値の数を固定サイズのメモリで推定する HyperLogLog
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        HyperLogLog { registers: vec![0; 1 << HLL_PRECISION] }
+    }
+
+    fn add(&mut self, value: &[u8]) {
+        // FNV-1a のハッシュ値を SplitMix64 で攪拌して上位ビットも一様にする
+        let hash = util::Rng::new(util::fnv1a64(value)).next_u64();
+        let idx = (hash >> (64 - HLL_PRECISION)) as usize;
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() as u8 + 1;
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-i32::from(*r))).sum();
+        let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        let zeros = self.registers.len() - self.registers.iter().filter(|r| **r > 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // 推定値が小さい場合は Linear Counting で補正する
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// 1カラムの集計の途中の値
+struct Profile {
+    value_type: Option<ValueType>,
+    count: u64,
+    nulls: u64,
+    distinct: Distinct,
+    /// バイト列として比較した最小値と最大値
+    min: Option<Vec<u8>>,
+    max: Option<Vec<u8>>,
+    /// 数値として解釈できる値の数、最小値、最大値、平均、偏差平方和(Welford法)
+    numbers: u64,
+    min_number: f64,
+    max_number: f64,
+    mean: f64,
+    m2: f64,
+    /// 分位数を求めるための数値の標本(Reservoir sampling)
+    sample: Vec<f64>,
+    rng: util::Rng,
+    max_bytes: usize,
+    max_chars: usize,
+    top: SpaceSaving,
+}
+
+impl Profile {
+    fn new(cfg: &Config, seed: u64) -> Self {
+        Profile {
+            value_type: None,
+            count: 0,
+            nulls: 0,
+            distinct: if cfg.approx_distinct { Distinct::Approx(HyperLogLog::new()) } else { Distinct::Exact(HashSet::new()) },
+            min: None,
+            max: None,
+            numbers: 0,
+            min_number: std::f64::INFINITY,
+            max_number: std::f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            sample: Vec::new(),
+            rng: util::Rng::new(seed),
+            max_bytes: 0,
+            max_chars: 0,
+            top: SpaceSaving::new(TOP_CAPACITY),
+        }
+    }
+
+    fn add(&mut self, value: &[u8]) {
+        self.count += 1;
+        if is_null(value) {
+            self.nulls += 1;
+            return;
+        }
+        let value_type = ValueType::infer(value);
+        self.value_type = Some(self.value_type.map_or(value_type, |current| current.merge(value_type)));
+        match self.distinct {
+            Distinct::Exact(ref mut values) => {
+                if !values.contains(value) {
+                    values.insert(value.to_vec());
+                }
+            }
+            Distinct::Approx(ref mut hll) => hll.add(value),
+        }
+        if self.min.as_ref().map_or(true, |min| value < min.as_slice()) {
+            self.min = Some(value.to_vec());
+        }
+        if self.max.as_ref().map_or(true, |max| value > max.as_slice()) {
+            self.max = Some(value.to_vec());
+        }
+        if value_type.is_numeric() {
+            let n: f64 = std::str::from_utf8(value).unwrap().trim().parse().unwrap();
+            self.numbers += 1;
+            self.min_number = self.min_number.min(n);
+            self.max_number = self.max_number.max(n);
+            let delta = n - self.mean;
+            self.mean += delta / self.numbers as f64;
+            self.m2 += delta * (n - self.mean);
+            if self.sample.len() < RESERVOIR_SIZE {
+                self.sample.push(n);
+            } else {
+                let i = self.rng.below(self.numbers as usize);
+                if i < RESERVOIR_SIZE {
+                    self.sample[i] = n;
+                }
+            }
+        }
+        self.max_bytes = self.max_bytes.max(value.len());
+        // UTF-8の継続バイト以外を数える
+        self.max_chars = self.max_chars.max(value.iter().filter(|c| (**c & 0xC0) != 0x80).count());
+        self.top.add(value.to_vec());
+    }
+
+    /// 集計結果を (項目名, 値) の一覧で返す。値は数値の場合はNumber、それ以外はTextとする
+    fn summary(&mut self, name: &str, top: usize) -> Vec<(&'static str, Item)> {
+        let numeric = self.value_type.map_or(false, |value_type| value_type.is_numeric());
+        let text = |value: &Option<Vec<u8>>| value.as_ref().map_or(Item::Null, |value| Item::Text(String::from_utf8_lossy(value).into_owned()));
+        let mut items = vec![
+            ("column", Item::Text(name.to_string())),
+            ("type", self.value_type.map_or(Item::Null, |value_type| Item::Text(value_type.name().to_string()))),
+            ("count", Item::Number(self.count as f64)),
+            ("null", Item::Number(self.nulls as f64)),
+            ("distinct", Item::Number(match self.distinct {
+                Distinct::Exact(ref values) => values.len() as u64,
+                Distinct::Approx(ref hll)   => hll.estimate(),
+            } as f64)),
+        ];
+        if numeric {
+            let stddev = (self.m2 / self.numbers as f64).sqrt();
+            items.push(("min", Item::Number(self.min_number)));
+            items.push(("max", Item::Number(self.max_number)));
+            items.push(("mean", Item::Number(self.mean)));
+            items.push(("stddev", Item::Number(stddev)));
+            self.sample.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            for (name, q) in QUANTILES {
+                let i = (q * (self.sample.len() - 1) as f64).round() as usize;
+                items.push((name, Item::Number(self.sample[i])));
+            }
+        } else {
+            items.push(("min", text(&self.min)));
+            items.push(("max", text(&self.max)));
+            for name in &["mean", "stddev"] {
+                items.push((name, Item::Null));
+            }
+            for (name, _) in QUANTILES {
+                items.push((name, Item::Null));
+            }
+        }
+        items.push(("max_bytes", Item::Number(self.max_bytes as f64)));
+        items.push(("max_chars", Item::Number(self.max_chars as f64)));
+        let values = self.top.top(top).into_iter()
+            .map(|(value, count)| (String::from_utf8_lossy(value).into_owned(), count))
+            .collect();
+        items.push(("top", Item::Top(values)));
+        items
+    }
+}
+
+/// 集計結果の1項目の値
+enum Item {
+    Null,
+    Number(f64),
+    Text(String),
+    /// (値, 出現回数) の一覧
+    Top(Vec<(String, u64)>),
+}
+
+impl Item {
+    fn to_text(&self) -> String {
+        match self {
+            Item::Null      => String::new(),
+            Item::Number(n) => format_number(*n),
+            Item::Text(s)   => s.clone(),
+            Item::Top(values) => {
+                let values: Vec<String> = values.iter().map(|(value, count)| format!("{}({})", value, count)).collect();
+                values.join("|")
+            }
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            Item::Null      => String::from("null"),
+            Item::Number(n) => format_number(*n),
+            Item::Text(s)   => util::json_string(s),
+            Item::Top(values) => {
+                let values: Vec<String> = values.iter()
+                    .map(|(value, count)| format!("{{\"value\":{},\"count\":{}}}", util::json_string(value), count))
+                    .collect();
+                format!("[{}]", util::join(',', &values))
+            }
+        }
+    }
+}
+
+/// 数値を小数点以下6桁までの文字列に整形する。末尾の0は出力しない
+fn format_number(n: f64) -> String {
+    let s = format!("{:.6}", n);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { String::from("0") } else { s.to_string() }
+}
+
+/// readerから読み込んだ行のcfgのカラムごとの集計結果をwriterに書き込む
+///
+/// 集計項目は型(int, float, date, string)、行数、nullの数、重複を除いた値の数、最小値、最大値、
+/// 平均、標準偏差、分位数(p25, p50, p75)、最大バイト数、最大文字数、頻出値。
+/// 空文字列と null, NULL, \N はnullとみなし、null以外の値を集計する。
+/// 平均、標準偏差、分位数は全ての値が数値の場合のみ出力する。分位数は最大10000件の標本から求めた近似値となる。
+///
+/// # Arguments
+/// * `reader` - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `cfg`    - 集計するカラムや出力形式の設定
+pub fn mstats<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, cfg: &Config) -> io::Result<()> {
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    // 乱数のシードを固定して、同じ入力に対して同じ結果を出力する
+    let mut profiles: Vec<Profile> = cfg.columns.iter().map(|idx| Profile::new(cfg, *idx as u64)).collect();
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        for (profile, idx) in profiles.iter_mut().zip(&cfg.columns) {
+            // カラムが足りない行は空文字列とみなす
+            profile.add(record.field(*idx));
+        }
+    }
+    let summaries: Vec<Vec<(&str, Item)>> = profiles.iter_mut().zip(&cfg.names).map(|(profile, name)| profile.summary(name, cfg.top)).collect();
+    match cfg.format {
+        Format::Table => {
+            if let Some(summary) = summaries.first() {
+                let names: Vec<&str> = summary.iter().map(|(name, _)| *name).collect();
+                writeln!(writer, "{}", util::join(cfg.delimiter as char, &names))?;
+            }
+            for summary in &summaries {
+                let values: Vec<String> = summary.iter().map(|(_, item)| item.to_text()).collect();
+                writeln!(writer, "{}", util::join(cfg.delimiter as char, &values))?;
+            }
+        }
+        Format::Json => {
+            let objects: Vec<String> = summaries.iter().map(|summary| {
+                let fields: Vec<String> = summary.iter().map(|(name, item)| format!("\"{}\":{}", name, item.to_json())).collect();
+                format!("{{{}}}", util::join(',', &fields))
+            }).collect();
+            writeln!(writer, "[{}]", util::join(',', &objects))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(data: &str, fields: Option<&str>, format: Format) -> String {
+        let mut reader = data.as_bytes();
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = Config::parse_fields(first_line.trim_end(), b',', fields, false);
+        cfg.format = format;
+        cfg.top = 2;
+        let mut writer = Vec::new();
+        mstats(&mut reader, &mut writer, &cfg).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn test_infer() {
+        assert_eq!(ValueType::Int, ValueType::infer(b"-12"));
+        assert_eq!(ValueType::Float, ValueType::infer(b"1.5e3"));
+        assert_eq!(ValueType::String, ValueType::infer(b"NaN"));
+        assert_eq!(ValueType::Date, ValueType::infer(b"2019-12-01"));
+        assert_eq!(ValueType::Date, ValueType::infer(b"2019/12/01 10:30:00"));
+        assert_eq!(ValueType::String, ValueType::infer(b"2019-13-01"));
+        assert_eq!(ValueType::String, ValueType::infer(b"2019-12/01"));
+        assert_eq!(ValueType::String, ValueType::infer(b"2019-12-01x"));
+        assert_eq!(ValueType::Float, ValueType::Int.merge(ValueType::Float));
+        assert_eq!(ValueType::String, ValueType::Int.merge(ValueType::Date));
+    }
+
+    #[test]
+    fn test_mstats() {
+        let data = "id,title,price,date\n1,りんご,120,2019-12-01\n2,みかん,80.5,2019-12-02\n3,りんご,,2019-12-01\n4,NULL,300\n";
+        let expected = "column,type,count,null,distinct,min,max,mean,stddev,p25,p50,p75,max_bytes,max_chars,top\n\
+                        id,int,4,0,4,1,4,2.5,1.118034,2,3,3,1,1,1(1)|2(1)\n\
+                        title,string,4,1,2,みかん,りんご,,,,,,9,3,りんご(2)|みかん(1)\n\
+                        price,float,4,1,3,80.5,300,166.833333,95.53388,120,120,300,4,4,120(1)|300(1)\n\
+                        date,date,4,1,2,2019-12-01,2019-12-02,,,,,,10,10,2019-12-01(2)|2019-12-02(1)\n";
+        assert_eq!(expected, stats(data, None, Format::Table));
+        let expected = "[{\"column\":\"price\",\"type\":\"float\",\"count\":2,\"null\":0,\"distinct\":2,\
+                        \"min\":-1,\"max\":2.25,\"mean\":0.625,\"stddev\":1.625,\"p25\":-1,\"p50\":2.25,\"p75\":2.25,\
+                        \"max_bytes\":4,\"max_chars\":4,\"top\":[{\"value\":\"-1\",\"count\":1},{\"value\":\"2.25\",\"count\":1}]},\
+                        {\"column\":\"title\",\"type\":null,\"count\":2,\"null\":2,\"distinct\":0,\"min\":null,\"max\":null,\
+                        \"mean\":null,\"stddev\":null,\"p25\":null,\"p50\":null,\"p75\":null,\"max_bytes\":0,\"max_chars\":0,\"top\":[]}]\n";
+        assert_eq!(expected, stats("title,price\n,-1\n\\N,2.25\n", Some("price,title"), Format::Json));
+    }
+
+    #[test]
+    fn test_hyperloglog() {
+        let mut hll = HyperLogLog::new();
+        for i in 0..100_000 {
+            hll.add(format!("value{}", i % 50_000).as_bytes());
+        }
+        let estimate = hll.estimate() as f64;
+        assert!((estimate - 50_000.0).abs() / 50_000.0 < 0.03, "{}", estimate);
+        let mut hll = HyperLogLog::new();
+        for i in 0..100 {
+            hll.add(format!("{}", i).as_bytes());
+        }
+        assert_eq!(100, hll.estimate());
+    }
+}
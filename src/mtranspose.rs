@@ -0,0 +1,197 @@
+//! ファイルの行と列を入れ替える
+extern crate memchr;
+
+use crate::reader::{Reader, Record, RecordBuf};
+use crate::util::TempDir;
+use std::cmp;
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufWriter, SeekFrom};
+use std::path::PathBuf;
+
+/// 省メモリ版で1行ごとに一時ファイルから読み込むバイト数
+const CURSOR_BUFFER_SIZE: usize = 4096;
+
+/// 行と列の入れ替えの設定
+pub struct Config {
+    pub delimiter: u8,
+    /// trueの場合、行を一時ファイルに書き出し、1行ごとに CURSOR_BUFFER_SIZE バイトずつ読み込みながら出力する。
+    /// (メモリ使用量は行数に比例し、ファイルの大きさによらない。列数が多く行数の少ないファイル向け)
+    pub low_memory: bool,
+    /// low_memory の場合に一時ファイルを作成するディレクトリ
+    pub temp_dir: PathBuf,
+    /// この文字列で始まる行はコメント行とみなし、出力しない
+    pub comment_prefix: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// # Arguments
+    /// * `delimiter` - 区切り文字
+    pub fn new(delimiter: u8) -> Self {
+        Config { delimiter, low_memory: false, temp_dir: env::temp_dir(), comment_prefix: None }
+    }
+}
+
+/// ヘッダ行と、readerから読み込んだ行の行と列を入れ替えてwriterに書き込む
+///
+/// 入力のn番目のカラムが出力のn行目になり、ヘッダのカラム名は出力の1列目になる。
+/// カラム数が行によって異なる場合は、最もカラム数の多い行に合わせて足りないカラムを空文字列で埋める。
+///
+/// # Arguments
+/// * `reader`     - ヘッダ行を読み込んだ後のreader
+/// * `writer`
+/// * `first_line` - ヘッダ行
+/// * `cfg`        - 区切り文字や一時ファイルの設定
+pub fn mtranspose<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, first_line: &str, cfg: &Config) -> io::Result<()> {
+    if cfg.low_memory {
+        transpose_low_memory(reader, writer, first_line, cfg)
+    } else {
+        transpose_in_memory(reader, writer, first_line, cfg)
+    }
+}
+
+/// 全ての行をメモリ上に読み込んでから行と列を入れ替える
+fn transpose_in_memory<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, first_line: &str, cfg: &Config) -> io::Result<()> {
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let mut rows: Vec<RecordBuf> = vec![RecordBuf::new(first_line.as_bytes().to_vec(), cfg.delimiter)];
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        rows.push(record.to_buf());
+    }
+    let width = rows.iter().map(|row| row.as_record().len()).max().unwrap_or(0);
+    for i in 0..width {
+        for (j, row) in rows.iter().enumerate() {
+            if j > 0 {
+                writer.write_all(&[cfg.delimiter])?;
+            }
+            // カラムが足りない行は空文字列とみなす
+            writer.write_all(row.as_record().field(i))?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// 一時ファイル上の1行を先頭のカラムから順に読み込むカーソル
+struct Cursor {
+    /// 次に読み込む一時ファイル上の位置
+    offset: u64,
+    buf: Vec<u8>,
+    /// buf の読み込み済みの位置
+    pos: usize,
+    /// 行末まで読み込んだかどうか
+    ended: bool,
+}
+
+impl Cursor {
+    /// 次のカラムの値をwriterに書き込む。行末まで読み込んでいる場合は何も書き込まない
+    fn copy_field<W: Write>(&mut self, file: &mut File, writer: &mut W, delimiter: u8) -> io::Result<()> {
+        while !self.ended {
+            if self.pos == self.buf.len() {
+                self.buf.resize(CURSOR_BUFFER_SIZE, 0);
+                file.seek(SeekFrom::Start(self.offset))?;
+                let n = file.read(&mut self.buf)?;
+                self.buf.truncate(n);
+                self.pos = 0;
+                self.offset += n as u64;
+                if n == 0 {
+                    self.ended = true;
+                    break;
+                }
+            }
+            let rest = &self.buf[self.pos..];
+            match memchr::memchr2(delimiter, b'\n', rest) {
+                Some(i) => {
+                    writer.write_all(&rest[..i])?;
+                    self.ended = rest[i] == b'\n';
+                    self.pos += i + 1;
+                    break;
+                }
+                None => {
+                    writer.write_all(rest)?;
+                    self.pos = self.buf.len();
+                }
+            }
+        }
+        if self.ended {
+            self.buf = Vec::new();
+        }
+        Ok(())
+    }
+}
+
+/// 行を一時ファイルに書き出してから、1行ごとのカーソルで少しずつ読み込みながら行と列を入れ替える
+fn transpose_low_memory<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, first_line: &str, cfg: &Config) -> io::Result<()> {
+    let comment_prefix = cfg.comment_prefix.as_ref().map(|prefix| &prefix[..]);
+    let dir = TempDir::new(&cfg.temp_dir, "transpose")?;
+    let path = dir.path.join("rows");
+    let mut temp = BufWriter::new(File::create(&path)?);
+    let mut cursors: Vec<Cursor> = Vec::new();
+    let mut width = 0;
+    let mut offset: u64 = 0;
+    let mut add_row = |record: &Record| -> io::Result<()> {
+        cursors.push(Cursor { offset, buf: Vec::new(), pos: 0, ended: false });
+        width = cmp::max(width, record.len());
+        temp.write_all(record.as_bytes())?;
+        temp.write_all(b"\n")?;
+        offset += record.as_bytes().len() as u64 + 1;
+        Ok(())
+    };
+    add_row(&RecordBuf::new(first_line.as_bytes().to_vec(), cfg.delimiter).as_record())?;
+    let mut reader = Reader::new(reader, cfg.delimiter).with_comment_prefix(comment_prefix);
+    while let Some(record) = reader.read_record()? {
+        add_row(&record)?;
+    }
+    temp.flush()?;
+    drop(temp);
+    let mut file = File::open(&path)?;
+    for _ in 0..width {
+        for (j, cursor) in cursors.iter_mut().enumerate() {
+            if j > 0 {
+                writer.write_all(&[cfg.delimiter])?;
+            }
+            cursor.copy_field(&mut file, writer, cfg.delimiter)?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transpose(data: &str, low_memory: bool) -> String {
+        let mut reader = data.as_bytes();
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = Config::new(b',');
+        cfg.low_memory = low_memory;
+        cfg.comment_prefix = Some(b"#".to_vec());
+        let mut writer = Vec::new();
+        mtranspose(&mut reader, &mut writer, first_line.trim_end(), &cfg).unwrap();
+        String::from_utf8(writer).unwrap()
+    }
+
+    #[test]
+    fn test_mtranspose() {
+        for low_memory in &[false, true] {
+            assert_eq!("id,1,2\nname,a,b\n", transpose("id,name\n1,a\n# comment\n2,b\n", *low_memory));
+            // カラム数が異なる行は空文字列で埋める
+            assert_eq!("id,1,2,,4\nname,a,,,d\n,,,,x\n", transpose("id,name\n1,a\n2\n\n4,d,x\n", *low_memory));
+            assert_eq!("id\nname\n", transpose("id,name\n", *low_memory));
+        }
+    }
+
+    #[test]
+    fn test_mtranspose_long_field() {
+        // CURSOR_BUFFER_SIZE を超える値も分割せずに出力する
+        let long = "x".repeat(CURSOR_BUFFER_SIZE * 2 + 1);
+        let data = format!("a,b,c\n{},1,{}\n2,{},3\n", long, long, long);
+        let expected = format!("a,{},2\nb,1,{}\nc,{},3\n", long, long, long);
+        assert_eq!(expected, transpose(&data, false));
+        assert_eq!(expected, transpose(&data, true));
+    }
+}
@@ -0,0 +1,157 @@
+//! 設定ファイルに保存したオプションのプロファイル
+//!
+//! 設定ファイルには以下のように `[profile.プロファイル名]` のテーブルでオプションを記述する。
+//!
+//! ```toml
+//! [profile.partner_a]
+//! delimiter = ","
+//! fields = "id,title,narrow1:-"
+//! ignore_case = true
+//! ```
+extern crate toml;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// プロファイルに指定できるキーと、対応するコマンドライン引数
+const KEYS: &[(&str, &str)] = &[
+    ("delimiter", "-d"),
+    ("fields", "-F"),
+    ("field_numbers", "-f"),
+    ("encoding", "--encoding"),
+    ("no_header", "--no-header"),
+    ("unique_header", "--unique-header"),
+    ("ignore_case", "--ignore-case"),
+    ("skip_lines", "--skip-lines"),
+    ("header_regex", "--header-regex"),
+    ("comment_prefix", "--comment-prefix"),
+    ("pass_comments", "--pass-comments"),
+];
+
+/// 設定ファイルを探すパスを優先度の高い順に返す
+///
+/// カレントディレクトリの .mtools.toml, $XDG_CONFIG_HOME/mtools/config.toml
+/// ($XDG_CONFIG_HOME が未設定の場合は ~/.config/mtools/config.toml) の順に探す。
+pub fn default_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(".mtools.toml")];
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("mtools").join("config.toml"));
+    }
+    paths
+}
+
+/// 設定ファイルからプロファイルを読み込み、コマンドライン引数と同じ形式のオプションに変換する
+///
+/// プロファイルは paths の先頭から順に探し、最初に見つかったものを返す。
+/// 存在しないファイルは無視し、読み込めないファイル(UTF-8でないファイルなど)はエラーとする。
+///
+/// # Arguments
+/// * `name`  - プロファイル名
+/// * `paths` - 設定ファイルのパス
+pub fn load(name: &str, paths: &[PathBuf]) -> Result<HashMap<String, String>, String> {
+    for path in paths {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("設定ファイルの読み込みに失敗しました: {}\n{}", path.display(), e)),
+        };
+        let profile = parse(&content, name)
+            .map_err(|e| format!("設定ファイルの読み込みに失敗しました: {}\n{}", path.display(), e))?;
+        if let Some(options) = profile {
+            return Ok(options);
+        }
+    }
+    let paths: Vec<String> = paths.iter().map(|path| path.display().to_string()).collect();
+    Err(format!("プロファイルが見つかりません: {}\n探索したファイル: {}", name, paths.join(", ")))
+}
+
+/// 設定ファイルの内容から name のプロファイルを取り出し、コマンドライン引数と同じ形式のオプションに変換する
+///
+/// プロファイルが存在しない場合はNoneを返す。
+///
+/// # Arguments
+/// * `content` - 設定ファイルの内容
+/// * `name`    - プロファイル名
+pub fn parse(content: &str, name: &str) -> Result<Option<HashMap<String, String>>, String> {
+    let root: toml::Value = content.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    let table = match root.get("profile").and_then(|profiles| profiles.get(name)) {
+        Some(toml::Value::Table(table)) => table,
+        Some(_) => return Err(format!("プロファイル {} はテーブルで記述してください", name)),
+        None    => return Ok(None),
+    };
+    let mut options = HashMap::new();
+    for (key, value) in table {
+        let option = match KEYS.iter().find(|(k, _)| k == key) {
+            Some((_, option)) => option.to_string(),
+            None => {
+                let keys: Vec<&str> = KEYS.iter().map(|(k, _)| *k).collect();
+                return Err(format!("不明なキー: {}\n指定できるキー: {}", key, keys.join(", ")));
+            }
+        };
+        match value {
+            toml::Value::String(s) => {
+                options.insert(option, s.clone());
+            }
+            toml::Value::Integer(n) if *n >= 0 => {
+                options.insert(option, n.to_string());
+            }
+            toml::Value::Boolean(true) => {
+                // フラグのオプションはコマンドライン引数と同じくオプション名を値とする
+                options.insert(option.clone(), option);
+            }
+            toml::Value::Boolean(false) => {}
+            _ => return Err(format!("{} の値が不正です: {}", key, value)),
+        }
+    }
+    Ok(Some(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[profile.partner_a]
+delimiter = ","
+fields = "id,title"
+skip_lines = 2
+no_header = true
+ignore_case = false
+
+[profile.partner_b]
+field_numbers = "0,3"
+"#;
+
+    #[test]
+    fn test_parse_1() {
+        let options = parse(CONFIG, "partner_a").unwrap().unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("-d".to_string(), ",".to_string());
+        expected.insert("-F".to_string(), "id,title".to_string());
+        expected.insert("--skip-lines".to_string(), "2".to_string());
+        expected.insert("--no-header".to_string(), "--no-header".to_string());
+        assert_eq!(expected, options);
+
+        let options = parse(CONFIG, "partner_b").unwrap().unwrap();
+        assert_eq!(Some(&"0,3".to_string()), options.get("-f"));
+        assert_eq!(None, parse(CONFIG, "partner_c").unwrap());
+    }
+
+    #[test]
+    fn test_parse_2() {
+        let err = parse("[profile.a]\nfield = \"id\"\n", "a").unwrap_err();
+        assert!(err.starts_with("不明なキー: field\n"));
+        let err = parse("[profile.a]\nskip_lines = -1\n", "a").unwrap_err();
+        assert_eq!("skip_lines の値が不正です: -1", err);
+        let err = parse("[profile]\na = 1\n", "a").unwrap_err();
+        assert_eq!("プロファイル a はテーブルで記述してください", err);
+        assert!(parse("[profile.a\n", "a").is_err());
+    }
+}
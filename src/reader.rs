@@ -0,0 +1,303 @@
+extern crate memchr;
+
+use crate::mcut::Config;
+use std::io;
+use std::io::prelude::*;
+
+/// 区切り文字で区切られたファイルを1行ずつ読み込むreader
+///
+/// 読み込んだ行は内部のバッファに格納され、各カラムはバッファを借用したスライスとして取得できる。
+/// 各サブコマンド(sort, join, agg など)はヘッダ行を読み込んだ後の入力をこのreaderで読み込む。
+///
+/// ```
+/// use mtools::reader::Reader;
+///
+/// let data = "id,title\n01,東京都\n02,大阪府\n";
+/// let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+/// let title = reader.index_of("title").unwrap();
+/// while let Some(record) = reader.read_record().unwrap() {
+///     println!("{}", String::from_utf8_lossy(record.get(title).unwrap()));
+/// }
+/// ```
+pub struct Reader<R> {
+    reader: R,
+    delimiter: u8,
+    header: Option<Vec<String>>,
+    // この文字列で始まる行はコメント行とみなし、読み飛ばす
+    comment_prefix: Option<Vec<u8>>,
+    // 読み込んだ行を格納する配列
+    buf: Vec<u8>,
+    // 各カラムの開始位置を格納する配列。末尾は行末 + 1
+    split: Vec<usize>,
+    // 最後に読み込んだ行の行番号(1始まり)
+    line_no: usize,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// 1行目をヘッダとみなさずにreaderを生成する
+    ///
+    /// # Arguments
+    /// * `reader`
+    /// * `delimiter` - 区切り文字
+    pub fn new(reader: R, delimiter: u8) -> Self {
+        Reader { reader, delimiter, header: None, comment_prefix: None, buf: Vec::new(), split: Vec::new(), line_no: 0 }
+    }
+
+    /// 1行目をヘッダとして読み込んでreaderを生成する
+    ///
+    /// # Arguments
+    /// * `reader`
+    /// * `delimiter` - 区切り文字
+    pub fn with_header(reader: R, delimiter: u8) -> io::Result<Self> {
+        let mut ret = Self::new(reader, delimiter);
+        let header: Vec<String> = match ret.read_record()? {
+            Some(record) => record.iter().map(|e| String::from_utf8_lossy(e).into_owned()).collect(),
+            None         => Vec::new(),
+        };
+        ret.header = Some(header);
+        Ok(ret)
+    }
+
+    /// コメント行を読み飛ばすreaderにする
+    ///
+    /// # Arguments
+    /// * `comment_prefix` - この文字列で始まる行はコメント行とみなす。Noneの場合は全ての行を読み込む
+    pub fn with_comment_prefix(self, comment_prefix: Option<&[u8]>) -> Self {
+        Reader { comment_prefix: comment_prefix.map(|prefix| prefix.to_vec()), ..self }
+    }
+
+    /// ヘッダのカラム名の配列を返す
+    pub fn header(&self) -> Option<&[String]> {
+        self.header.as_ref().map(|header| &header[..])
+    }
+
+    /// カラム名もしくはカラム番号に対応するカラムのindexを返す
+    ///
+    /// カラム名の解決は -F オプションと同様に「カラム名#n」の指定にも対応する。
+    ///
+    /// # Arguments
+    /// * `name` - カラム名もしくは0から始まるカラム番号
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        find_index(self.header.as_ref()?, name)
+    }
+
+    /// 最後に読み込んだ行の行番号(1始まり)を返す
+    pub fn line_no(&self) -> usize {
+        self.line_no
+    }
+
+    /// 1行読み込む。コメント行は読み飛ばす。ファイルの末尾に達した場合はNoneを返す
+    pub fn read_record(&mut self) -> io::Result<Option<Record>> {
+        loop {
+            self.buf.clear();
+            if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+                return Ok(None);
+            }
+            self.line_no += 1;
+            if !self.comment_prefix.as_ref().map_or(false, |prefix| self.buf.starts_with(prefix)) {
+                break;
+            }
+        }
+        if self.buf.last() == Some(&b'\n') {
+            self.buf.pop();
+            if self.buf.last() == Some(&b'\r') {
+                self.buf.pop();
+            }
+        }
+        split_line(&self.buf, self.delimiter, &mut self.split);
+        Ok(Some(Record { buf: &self.buf, split: &self.split, header: self.header.as_ref() }))
+    }
+}
+
+/// 行の各カラムの開始位置(区切り文字も含んだindex)をsplitに格納する。末尾は行末 + 1
+fn split_line(line: &[u8], delimiter: u8, split: &mut Vec<usize>) {
+    split.clear();
+    split.push(0);
+    split.extend(memchr::memchr_iter(delimiter, line).map(|position| position + 1));
+    split.push(line.len() + 1);
+}
+
+/// ヘッダのカラム名の配列から、カラム名もしくはカラム番号に対応するカラムのindexを返す
+fn find_index(header: &[String], name: &str) -> Option<usize> {
+    let header: Vec<&str> = header.iter().map(|e| e.as_str()).collect();
+    Config::find_column(name, &header, false)
+}
+
+/// Readerで読み込んだ1行
+#[derive(Clone, Copy)]
+pub struct Record<'a> {
+    buf: &'a [u8],
+    split: &'a [usize],
+    header: Option<&'a Vec<String>>,
+}
+
+impl<'a> Record<'a> {
+    /// カラム数を返す
+    pub fn len(&self) -> usize {
+        self.split.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// idx番目のカラムの値を返す
+    ///
+    /// # Arguments
+    /// * `idx` - 0から始まるカラム番号
+    pub fn get(&self, idx: usize) -> Option<&'a [u8]> {
+        self.span(idx).map(|(start, end)| &self.buf[start..end])
+    }
+
+    /// idx番目のカラムの値を返す。カラムが足りない場合は空文字列を返す
+    ///
+    /// # Arguments
+    /// * `idx` - 0から始まるカラム番号
+    pub fn field(&self, idx: usize) -> &'a [u8] {
+        self.get(idx).unwrap_or(&[])
+    }
+
+    /// idx番目のカラムの行内での(開始位置, 終了位置)を返す
+    ///
+    /// # Arguments
+    /// * `idx` - 0から始まるカラム番号
+    pub fn span(&self, idx: usize) -> Option<(usize, usize)> {
+        if idx + 1 < self.split.len() {
+            Some((self.split[idx], self.split[idx + 1] - 1))
+        } else {
+            None
+        }
+    }
+
+    /// カラム名もしくはカラム番号に対応するカラムの値を返す
+    ///
+    /// カラム名の解決は Reader::index_of と同じ。呼び出すたびにカラム名を解決するので、
+    /// 多くの行から同じカラムを取得する場合は index_of で求めたindexを get に指定する。
+    ///
+    /// # Arguments
+    /// * `name` - カラム名もしくは0から始まるカラム番号
+    pub fn get_by_name(&self, name: &str) -> Option<&'a [u8]> {
+        self.get(find_index(self.header?, name)?)
+    }
+
+    /// ヘッダのカラム名の配列を返す
+    pub fn header(&self) -> Option<&'a [String]> {
+        self.header.map(|header| &header[..])
+    }
+
+    /// 改行を除いた行全体を返す
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// 各カラムの値を先頭から順に返すイテレータを返す
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let buf = self.buf;
+        self.split.windows(2).map(move |w| &buf[w[0]..w[1] - 1])
+    }
+
+    /// 次の行を読み込んだ後も保持できるように、行をコピーしたRecordBufを返す
+    pub fn to_buf(&self) -> RecordBuf {
+        RecordBuf { buf: self.buf.to_vec(), split: self.split.to_vec() }
+    }
+}
+
+/// Recordの行をコピーして保持する1行。ハッシュ結合のように複数の行をメモリ上に保持する場合に使う
+#[derive(Clone, Debug)]
+pub struct RecordBuf {
+    buf: Vec<u8>,
+    split: Vec<usize>,
+}
+
+impl RecordBuf {
+    /// 改行を含まない行を区切り文字で分割する
+    ///
+    /// # Arguments
+    /// * `line`      - 改行を除いた行
+    /// * `delimiter` - 区切り文字
+    pub fn new(line: Vec<u8>, delimiter: u8) -> Self {
+        let mut split = Vec::new();
+        split_line(&line, delimiter, &mut split);
+        RecordBuf { buf: line, split }
+    }
+
+    /// 保持している行をRecordとして返す。(ヘッダは持たない)
+    pub fn as_record(&self) -> Record {
+        Record { buf: &self.buf, split: &self.split, header: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_record_1() {
+        let data = "id,title,kana\r\n01,東京都,トウキョウト\n02,,\n\n03";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+        assert_eq!(Some(&["id".to_string(), "title".to_string(), "kana".to_string()][..]), reader.header());
+        assert_eq!(Some(1), reader.index_of("title"));
+        assert_eq!(Some(2), reader.index_of("2"));
+        assert_eq!(None, reader.index_of("not_exists"));
+
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(3, record.len());
+        assert_eq!(Some("東京都".as_bytes()), record.get(1));
+        assert_eq!(Some("トウキョウト".as_bytes()), record.get_by_name("kana"));
+        assert_eq!(None, record.get(3));
+
+        let record = reader.read_record().unwrap().unwrap();
+        let fields: Vec<&[u8]> = record.iter().collect();
+        assert_eq!(vec![&b"02"[..], b"", b""], fields);
+
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(1, record.len());
+        assert_eq!(Some(&b""[..]), record.get(0));
+
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(b"03", record.as_bytes());
+        assert_eq!(5, reader.line_no());
+        assert!(reader.read_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_get_by_name() {
+        // 重複したカラム名は「カラム名#n」、カラム番号も index_of と同じく解決する
+        let data = "id,title,title\n01,東京都,トウキョウト\n";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap();
+        assert_eq!(Some(2), reader.index_of("title#2"));
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(Some("東京都".as_bytes()), record.get_by_name("title"));
+        assert_eq!(Some("トウキョウト".as_bytes()), record.get_by_name("title#2"));
+        assert_eq!(Some(&b"01"[..]), record.get_by_name("0"));
+        assert_eq!(None, record.get_by_name("title#3"));
+        assert_eq!(None, record.get_by_name("3"));
+    }
+
+    #[test]
+    fn test_read_record_comment() {
+        let data = "id,title\n# comment\n01,東京都,x\n02\n";
+        let mut reader = Reader::with_header(data.as_bytes(), b',').unwrap().with_comment_prefix(Some(b"#"));
+        let record = reader.read_record().unwrap().unwrap().to_buf();
+        assert_eq!(3, reader.line_no());
+        let record = record.as_record();
+        assert_eq!(Some((3, 12)), record.span(1));
+        assert_eq!(b"x", record.field(2));
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(b"", record.field(1));
+        assert_eq!(None, record.span(1));
+        assert!(reader.read_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_read_record_2() {
+        // ヘッダなし
+        let data = "a\tb\nc\td\n";
+        let mut reader = Reader::new(data.as_bytes(), b'\t');
+        assert_eq!(None, reader.header());
+        assert_eq!(None, reader.index_of("0"));
+        let record = reader.read_record().unwrap().unwrap();
+        assert_eq!(None, record.get_by_name("a"));
+        assert_eq!(Some(&b"b"[..]), record.get(1));
+    }
+}
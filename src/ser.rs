@@ -0,0 +1,452 @@
+//! 構造体を区切り文字で区切られた行としてシリアライズする (serde feature)
+extern crate serde;
+
+use self::serde::ser::{self, Impossible, Serialize};
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+
+/// シリアライズに失敗した場合のエラー
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Message(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e)      => e.fmt(f),
+            Error::Message(m) => f.write_str(m),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// 構造体を1行ずつ書き込むwriter
+///
+/// 最初に書き込んだ構造体のフィールド名からヘッダを生成して出力する。
+///
+/// ```
+/// use mtools::ser::Writer;
+/// use serde_derive::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Pref<'a> {
+///     id: u32,
+///     title: &'a str,
+/// }
+///
+/// let mut writer = Writer::new(Vec::new(), b',');
+/// writer.serialize(&Pref { id: 1, title: "東京都" }).unwrap();
+/// assert_eq!("id,title\n1,東京都\n", String::from_utf8(writer.into_inner()).unwrap());
+/// ```
+pub struct Writer<W: Write> {
+    writer: W,
+    delimiter: u8,
+    // ヘッダを出力する必要があればtrue
+    write_header: bool,
+    // 1行分の値を格納する配列
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    /// ヘッダを出力するwriterを生成する
+    ///
+    /// # Arguments
+    /// * `writer`
+    /// * `delimiter` - 区切り文字
+    pub fn new(writer: W, delimiter: u8) -> Self {
+        Writer { writer, delimiter, write_header: true, buf: Vec::new() }
+    }
+
+    /// ヘッダを出力しないwriterを生成する
+    ///
+    /// # Arguments
+    /// * `writer`
+    /// * `delimiter` - 区切り文字
+    pub fn without_header(writer: W, delimiter: u8) -> Self {
+        Writer { writer, delimiter, write_header: false, buf: Vec::new() }
+    }
+
+    /// 構造体を1行として書き込む。最初の1行の前にはヘッダを書き込む
+    ///
+    /// 値に区切り文字もしくは改行が含まれる場合はエラーを返す。
+    pub fn serialize<T: Serialize>(&mut self, record: &T) -> Result<(), Error> {
+        self.buf.clear();
+        let mut names: Vec<&'static str> = Vec::new();
+        record.serialize(RecordSerializer { buf: &mut self.buf, names: &mut names, delimiter: self.delimiter, len: 0 })?;
+        if self.write_header {
+            if names.is_empty() {
+                return Err(ser::Error::custom("フィールド名がないためヘッダを出力できません"));
+            }
+            let header: Vec<&[u8]> = names.iter().map(|e| e.as_bytes()).collect();
+            self.writer.write_all(&header.join(&self.delimiter))?;
+            self.writer.write_all(b"\n")?;
+            self.write_header = false;
+        }
+        self.buf.push(b'\n');
+        self.writer.write_all(&self.buf)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// 内部のwriterを返す
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// 1行全体のシリアライザ
+struct RecordSerializer<'a> {
+    buf: &'a mut Vec<u8>,
+    names: &'a mut Vec<&'static str>,
+    delimiter: u8,
+    // 書き込んだカラム数
+    len: usize,
+}
+
+impl<'a> RecordSerializer<'a> {
+    fn field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        if self.len > 0 {
+            self.buf.push(self.delimiter);
+        }
+        let start = self.buf.len();
+        value.serialize(FieldSerializer { buf: self.buf })?;
+        if self.buf[start..].iter().any(|e| *e == self.delimiter || *e == b'\n') {
+            return Err(ser::Error::custom(format!(
+                "区切り文字もしくは改行を含む値は出力できません: {}", String::from_utf8_lossy(&self.buf[start..])
+            )));
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn unsupported<T>(&self, type_name: &str) -> Result<T, Error> {
+        Err(ser::Error::custom(format!("{} は1行として出力できません", type_name)))
+    }
+}
+
+macro_rules! serialize_as_field {
+    ($($method:ident: $ty:ty,)*) => {
+        $(
+            fn $method(mut self, v: $ty) -> Result<(), Error> {
+                self.field(&v)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    // 構造体以外の値は1カラムの行として出力する
+    serialize_as_field! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+    }
+
+    fn serialize_none(mut self) -> Result<(), Error> {
+        self.field(&())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(mut self) -> Result<(), Error> {
+        self.field(&())
+    }
+
+    fn serialize_unit_struct(mut self, _name: &'static str) -> Result<(), Error> {
+        self.field(&())
+    }
+
+    fn serialize_unit_variant(mut self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), Error> {
+        self.field(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<(), Error> {
+        self.unsupported(name)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, Error> {
+        self.unsupported(name)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.unsupported("map")
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, Error> {
+        self.unsupported(name)
+    }
+}
+
+impl<'a> ser::SerializeSeq for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for RecordSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.names.push(key);
+        self.field(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// 1カラムの値のシリアライザ
+struct FieldSerializer<'a> {
+    buf: &'a mut Vec<u8>,
+}
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty,)*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                write!(self.buf, "{}", v)?;
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    serialize_display! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.buf.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, name: &'static str, _index: u32, _variant: &'static str, _value: &T) -> Result<(), Error> {
+        Err(nested(name))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(nested("seq"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(nested("tuple"))
+    }
+
+    fn serialize_tuple_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(nested(name))
+    }
+
+    fn serialize_tuple_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(nested(name))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(nested("map"))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(nested(name))
+    }
+
+    fn serialize_struct_variant(self, name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, Error> {
+        Err(nested(name))
+    }
+}
+
+fn nested(type_name: &str) -> Error {
+    ser::Error::custom(format!("ネストした値は1カラムとして出力できません: {}", type_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Serialize;
+
+    #[derive(Serialize)]
+    enum Region {
+        #[serde(rename = "関東")]
+        Kanto,
+    }
+
+    #[derive(Serialize)]
+    struct Pref<'a> {
+        id: u32,
+        title: &'a str,
+        kana: Option<String>,
+        narrow1: Option<Region>,
+        price: f64,
+    }
+
+    #[test]
+    fn test_serialize_1() {
+        let mut writer = Writer::new(Vec::new(), b',');
+        writer.serialize(&Pref { id: 1, title: "東京都", kana: None, narrow1: Some(Region::Kanto), price: 1.5 }).unwrap();
+        writer.serialize(&Pref { id: 2, title: "大阪府", kana: Some(String::from("オオサカフ")), narrow1: None, price: 2.0 }).unwrap();
+        let expected = "id,title,kana,narrow1,price\n1,東京都,,関東,1.5\n2,大阪府,オオサカフ,,2\n";
+        assert_eq!(expected, String::from_utf8(writer.into_inner()).unwrap());
+    }
+
+    #[test]
+    fn test_serialize_2() {
+        // ヘッダなし, タプル
+        let mut writer = Writer::without_header(Vec::new(), b'\t');
+        writer.serialize(&(1, "a", None::<u8>)).unwrap();
+        writer.serialize(&vec!["b", "c"]).unwrap();
+        assert_eq!("1\ta\t\nb\tc\n", String::from_utf8(writer.into_inner()).unwrap());
+    }
+
+    #[test]
+    fn test_serialize_3() {
+        let mut writer = Writer::new(Vec::new(), b',');
+        let err = writer.serialize(&Pref { id: 1, title: "a,b", kana: None, narrow1: None, price: 0.0 }).unwrap_err();
+        assert_eq!("区切り文字もしくは改行を含む値は出力できません: a,b", err.to_string());
+        // ヘッダを生成できない
+        let err = writer.serialize(&(1, 2)).unwrap_err();
+        assert_eq!("フィールド名がないためヘッダを出力できません", err.to_string());
+    }
+}
@@ -1,3 +1,10 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 /// seq を delimiter で連結した文字列に整形する
 ///
 /// # Arguments
@@ -11,7 +18,162 @@ pub fn join<T: std::fmt::Display>(delimiter: char, seq: &[T]) -> String {
         ret.push(delimiter);
     }
     ret.pop();
-    return ret;
+    ret
+}
+
+/// 2つの文字列の編集距離(レーベンシュタイン距離)を文字単位で計算する
+///
+/// # Arguments
+/// * `a` - 比較対象の文字列
+/// * `b` - 比較対象の文字列
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr: Vec<usize> = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = std::cmp::min(std::cmp::min(prev[j + 1] + 1, curr[j] + 1), prev[j] + cost);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// 文字列をJSONの文字列リテラルに変換する
+///
+/// # Arguments
+/// * `s` - 変換対象の文字列
+pub fn json_string(s: &str) -> String {
+    use std::fmt::Write;
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('"');
+    for c in s.chars() {
+        match c {
+            '"'  => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(ret, "\\u{:04x}", c as u32).unwrap(),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// バイト列のFNV-1a(64bit)ハッシュ値を計算する
+///
+/// # Arguments
+/// * `bytes` - ハッシュ値を計算するバイト列
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// 数字の並びを数値として比較する自然順で2つのバイト列を比較する
+///
+/// 数字以外の部分はバイト列として比較する。数値として等しい場合(「01」と「1」など)は桁数の少ない方を先とする。
+///
+/// # Arguments
+/// * `a` - 比較対象のバイト列
+/// * `b` - 比較対象のバイト列
+pub fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let (start_a, start_b) = (i, j);
+            while i < a.len() && a[i].is_ascii_digit() { i += 1; }
+            while j < b.len() && b[j].is_ascii_digit() { j += 1; }
+            let (num_a, num_b) = (&a[start_a..i], &b[start_b..j]);
+            // 先頭の0を除いた桁数、数字の並びの順に比較する
+            let trimmed_a = &num_a[num_a.iter().position(|c| *c != b'0').unwrap_or_else(|| num_a.len())..];
+            let trimmed_b = &num_b[num_b.iter().position(|c| *c != b'0').unwrap_or_else(|| num_b.len())..];
+            let ord = trimmed_a.len().cmp(&trimmed_b.len())
+                .then_with(|| trimmed_a.cmp(trimmed_b))
+                .then_with(|| num_a.len().cmp(&num_b.len()));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        } else {
+            if a[i] != b[j] {
+                return a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+/// 乱数生成器(SplitMix64)
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// 乱数のシードを指定して生成する
+    ///
+    /// # Arguments
+    /// * `seed` - 乱数のシード
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// 現在時刻から生成したシードを返す
+    pub fn seed_from_time() -> u64 {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+        now.as_secs() ^ u64::from(now.subsec_nanos()) << 32 ^ u64::from(std::process::id())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// [0, 1) の一様乱数を返す
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// [0, n) の一様な整数の乱数を返す
+    pub fn below(&mut self, n: usize) -> usize {
+        ((u128::from(self.next_u64()) * n as u128) >> 64) as usize
+    }
+}
+
+/// 一時ファイルを作成するディレクトリ。dropされた時にディレクトリごと削除する
+pub(crate) struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    /// parent の下に「mtools-{name}-{プロセスID}-{時刻}」のディレクトリを作成する
+    ///
+    /// # Arguments
+    /// * `parent` - 一時ディレクトリを作成するディレクトリ
+    /// * `name`   - ディレクトリ名に含めるサブコマンド名
+    pub fn new(parent: &Path, name: &str) -> io::Result<Self> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let path = parent.join(format!("mtools-{}-{}-{}", name, process::id(), nanos));
+        fs::create_dir_all(&path)?;
+        Ok(TempDir { path })
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
 }
 
 # [cfg(test)]
@@ -22,4 +184,48 @@ mod tests {
     fn test_join() {
         assert_eq!("1;2;3;4", join(';', &[1, 2, 3, 4]));
     }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("title", "title"));
+        assert_eq!(2, edit_distance("titel", "title"));
+        assert_eq!(3, edit_distance("", "abc"));
+        assert_eq!(1, edit_distance("カナ", "かナ"));
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!("\"abc\"", json_string("abc"));
+        assert_eq!("\"a\\\"b\\\\c\\td\\u0001\"", json_string("a\"b\\c\td\u{1}"));
+    }
+
+    #[test]
+    fn test_fnv1a64() {
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a64(b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a64(b"a"));
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(Ordering::Less, natural_cmp(b"file2.txt", b"file10.txt"));
+        assert_eq!(Ordering::Greater, natural_cmp(b"file10", b"file9"));
+        assert_eq!(Ordering::Less, natural_cmp(b"1", b"01"));
+        assert_eq!(Ordering::Less, natural_cmp(b"a", b"a1"));
+        assert_eq!(Ordering::Less, natural_cmp(b"a1b", b"a1c"));
+        assert_eq!(Ordering::Equal, natural_cmp(b"x007", b"x007"));
+    }
+
+    #[test]
+    fn test_rng() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+            let f = a.next_f64();
+            assert!(0.0 <= f && f < 1.0);
+            assert!(a.below(7) < 7);
+            b.next_f64();
+            b.below(7);
+        }
+    }
 }
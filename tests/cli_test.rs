@@ -0,0 +1,68 @@
+use std::env;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+
+#[test]
+fn test_join_stdin() {
+    // semi, anti でも両方を標準入力から読み込むことはできない
+    for join_type in &["semi", "anti"] {
+        for files in &[&[][..], &["-", "-"][..]] {
+            let mut args = vec!["join", "-d", ",", "-k", "id", "-t", join_type];
+            args.extend_from_slice(files);
+            let output = mtools(&args, "id,v\n1,a\n");
+            assert!(!output.status.success());
+            assert_eq!("標準入力から読み込めるのは一方のファイルのみです。\n", String::from_utf8(output.stderr).unwrap());
+        }
+    }
+    // 一方のみ標準入力の場合は結合できる
+    let data = "product_id,title\np1,a\np3,c\n";
+    let args = ["join", "-d", ",", "-k", "product_id", "-t", "semi", "-", "tests/mjoin_test/blocklist.csv"];
+    assert_eq!("product_id,title\np3,c\n", String::from_utf8(mtools(&args, data).stdout).unwrap());
+    let args = ["join", "-d", ",", "-k", "product_id", "-t", "anti", "-", "tests/mjoin_test/blocklist.csv"];
+    assert_eq!("product_id,title\np1,a\n", String::from_utf8(mtools(&args, data).stdout).unwrap());
+}
+
+#[test]
+fn test_help_after_terminator() {
+    // 「--」以降の「-h」はファイル名とみなす
+    let output = mtools(&["cut", "-d", ",", "-F", "id", "--", "-h"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("ファイルを開けません: -h\n"));
+    // オプションの値の「-h」はヘルプの指定とみなさない
+    let output = mtools(&["cut", "-d", ",", "-F", "-h"], "-h,id\n1,2\n");
+    assert!(output.status.success());
+    assert_eq!("-h\n1\n", String::from_utf8(output.stdout).unwrap());
+    // 他のオプションより後に指定した --help はヘルプを出力する
+    let output = mtools(&["cut", "-d", ",", "-F", "id", "--help"], "");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("-F, --fields <FIELDS>:"));
+}
+
+#[test]
+fn test_cat_files_and_stdin() {
+    // ヘッダ行を読み込んだ後に開き直したファイルと標準入力を順に連結する
+    let args = ["cat", "-d", ",", "--comment-prefix", "#", "tests/mcat_test/v1.csv", "-", "tests/mcat_test/v3.csv"];
+    let output = mtools(&args, "# comment\nid,title\n5,なし\n");
+    assert_eq!("id,title,price,category\n1,りんご,120,\n2,みかん,80,\n5,なし,,\n4,,,野菜\n", String::from_utf8(output.stdout).unwrap());
+}
+
+/// mtools をビルドした実行ファイルを、inputを標準入力として実行する
+fn mtools(args: &[&str], input: &str) -> Output {
+    // テストの実行ファイルは target/debug/deps に、mtools は target/debug に置かれる
+    let mut path: PathBuf = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    let mut child = Command::new(path.join("mtools"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // 入力を読み込まずに終了する場合もあるので、書き込みのエラーは無視する
+    child.stdin.take().unwrap().write_all(input.as_bytes()).ok();
+    child.wait_with_output().unwrap()
+}
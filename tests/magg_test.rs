@@ -0,0 +1,44 @@
+extern crate mtools;
+
+use mtools::magg;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_magg_1() {
+    let mut reader = BufReader::new(File::open("tests/magg_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let aggregates = "count(),total=sum(price),avg(price),max(price),concat(title,'|')";
+    let cfg = magg::Config::parse(line.trim_end(), b',', "narrow1", aggregates, false);
+    cfg.write_header(&mut writer).unwrap();
+    magg::magg(&mut reader, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/magg_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_magg_2() {
+    // 一時ファイルに書き出して集計する
+    let mut reader = BufReader::new(File::open("tests/magg_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut cfg = magg::Config::parse(line.trim_end(), b',', "DATE,Narrow1", "count(),count_distinct(title)", true);
+    cfg.max_groups = Some(1);
+    cfg.write_header(&mut writer).unwrap();
+    magg::magg(&mut reader, &mut writer, &cfg).unwrap();
+    let actual = String::from_utf8(writer).unwrap();
+    let mut lines: Vec<&str> = actual.lines().collect();
+    lines[1..].sort();
+    assert_eq!(read_all("tests/magg_test/expected_2.csv"), lines.join("\n") + "\n");
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
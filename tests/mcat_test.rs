@@ -0,0 +1,59 @@
+extern crate mtools;
+
+use mtools::mcat;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_mcat_1() {
+    let files = ["tests/mcat_test/v1.csv", "tests/mcat_test/v2.csv", "tests/mcat_test/v3.csv"];
+    let mut inputs: Vec<(BufReader<File>, String)> = files.iter().map(|file| {
+        let mut reader = BufReader::new(File::open(file).unwrap());
+        let line = read_line(&mut reader);
+        (reader, line)
+    }).collect();
+    let first_lines: Vec<&str> = inputs.iter().map(|(_, line)| line.as_str()).collect();
+    let mut cfg = mcat::Config::new(&first_lines, b',', mcat::Mode::Union, false);
+    cfg.default = b"NULL".to_vec();
+    cfg.source_column = Some(String::from("src"));
+    let mut writer = vec![];
+    cfg.write_header(&mut writer).unwrap();
+    for ((reader, line), file) in inputs.iter_mut().zip(&files) {
+        mcat::mcat(reader, &mut writer, line, file, &cfg).unwrap();
+    }
+    assert_eq!(read_all("tests/mcat_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_mcat_2() {
+    // 全てのファイルに存在するカラムのみ出力する
+    let files = ["tests/mcat_test/v1.csv", "tests/mcat_test/v2.csv"];
+    let mut inputs: Vec<(BufReader<File>, String)> = files.iter().map(|file| {
+        let mut reader = BufReader::new(File::open(file).unwrap());
+        let line = read_line(&mut reader);
+        (reader, line)
+    }).collect();
+    let first_lines: Vec<&str> = inputs.iter().map(|(_, line)| line.as_str()).collect();
+    let cfg = mcat::Config::new(&first_lines, b',', mcat::Mode::Intersection, false);
+    let mut writer = vec![];
+    cfg.write_header(&mut writer).unwrap();
+    for (reader, line) in inputs.iter_mut() {
+        mcat::mcat(reader, &mut writer, line, "", &cfg).unwrap();
+    }
+    assert_eq!(read_all("tests/mcat_test/expected_2.csv"), String::from_utf8(writer).unwrap());
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line.trim_end().to_string()
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
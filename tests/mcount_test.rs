@@ -0,0 +1,41 @@
+extern crate mtools;
+
+use mtools::mcount;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_mcount_1() {
+    let mut reader = BufReader::new(File::open("tests/mcount_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let cfg = mcount::Config::parse_fields(line.trim_end(), b',', "narrow1", false);
+    cfg.write_header(&mut writer).unwrap();
+    assert!(!mcount::mcount(&mut reader, &mut writer, &cfg).unwrap());
+    assert_eq!(read_all("tests/mcount_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_mcount_2() {
+    // 値の組み合わせの上位2件
+    let mut reader = BufReader::new(File::open("tests/mcount_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut cfg = mcount::Config::parse_fields(line.trim_end(), b',', "DATE,Narrow1", true);
+    cfg.top = Some(2);
+    cfg.capacity = Some(10);
+    cfg.write_header(&mut writer).unwrap();
+    assert!(!mcount::mcount(&mut reader, &mut writer, &cfg).unwrap());
+    assert_eq!(read_all("tests/mcount_test/expected_2.csv"), String::from_utf8(writer).unwrap());
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
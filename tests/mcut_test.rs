@@ -1,6 +1,7 @@
 extern crate mtools;
 
 use mtools::mcut;
+use regex::Regex;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::Cursor;
@@ -122,6 +123,200 @@ fn test_mcut_8() {
     assert_eq!(read_all("tests/mcut_test/expected_8.csv"), actual);
 }
 
+#[test]
+fn test_mcut_9() {
+    // 仮想カラム
+    let mut reader = BufReader::new(File::open("tests/mcut_test/data.csv").unwrap());
+    let mut writer = Cursor::new(vec![]);
+    let field = String::from("@filename,@lineno,@bytes_offset,id,@rowhash");
+    let delimiter = b',';
+    let line = (&mut reader).lines().next().unwrap().ok().unwrap();
+    let mut cfg = mcut::Config::parse_field_as_name(line.clone(), delimiter, field);
+    cfg.filename = String::from("data.csv");
+    cfg.write_header(&mut writer);
+    mcut::mcut(&mut reader, &mut writer, cfg);
+    let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+    assert_eq!(read_all("tests/mcut_test/expected_9.csv"), actual);
+}
+
+#[test]
+fn test_mcut_10() {
+    // number, 仮想カラム
+    let mut reader = BufReader::new(File::open("tests/mcut_test/data.csv").unwrap());
+    let mut writer = Cursor::new(vec![]);
+    let field = String::from("@lineno,0,@bytes_offset");
+    let delimiter = b',';
+    let line = (&mut reader).lines().next().unwrap().ok().unwrap();
+    let cfg = mcut::Config::parse_field_as_number(line.clone(), delimiter, field);
+    cfg.write_first_line(&mut writer);
+    mcut::mcut(&mut reader, &mut writer, cfg);
+    let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+    assert_eq!(read_all("tests/mcut_test/expected_10.csv"), actual);
+}
+
+#[test]
+fn test_mcut_11() {
+    // 行の範囲指定
+    let cases: Vec<(usize, Option<usize>, Option<usize>, &str)> = vec![
+        (1, None   , None   , "id,lineno\n02,3\n03,4\n04,5\n05,6\n"),
+        (0, Some(2), None   , "id,lineno\n01,2\n02,3\n"),
+        (1, Some(2), None   , "id,lineno\n02,3\n03,4\n"),
+        (0, None   , Some(2), "id,lineno\n04,5\n05,6\n"),
+        (1, Some(3), Some(2), "id,lineno\n03,4\n04,5\n"),
+        (0, Some(0), None   , "id,lineno\n"),
+        (0, None   , Some(0), "id,lineno\n"),
+        (10, None  , None   , "id,lineno\n"),
+    ];
+    for (skip, limit, tail, expected) in cases {
+        let mut reader = BufReader::new(File::open("tests/mcut_test/data.csv").unwrap());
+        let mut writer = Cursor::new(vec![]);
+        let line = (&mut reader).lines().next().unwrap().ok().unwrap();
+        let mut cfg = mcut::Config::parse_field_as_name(line.clone(), b',', String::from("id,@lineno"));
+        cfg.skip = skip;
+        cfg.limit = limit;
+        cfg.tail = tail;
+        cfg.write_header(&mut writer);
+        mcut::mcut(&mut reader, &mut writer, cfg);
+        let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+        assert_eq!(expected, actual, "skip: {}, limit: {:?}, tail: {:?}", skip, limit, tail);
+    }
+}
+
+#[test]
+fn test_mcut_12() {
+    // サンプリング
+    let cases: Vec<(mcut::Sample, Option<usize>, usize)> = vec![
+        (mcut::Sample::Rows(3)       , None   , 3),
+        (mcut::Sample::Rows(10)      , None   , 5),
+        (mcut::Sample::Rows(3)       , Some(2), 2),
+        (mcut::Sample::Rows(0)       , None   , 0),
+        (mcut::Sample::Fraction(1.0) , None   , 5),
+        (mcut::Sample::Fraction(0.0) , None   , 0),
+    ];
+    for (sample, tail, expected_len) in cases {
+        for seed in 0..10 {
+            let mut reader = BufReader::new(File::open("tests/mcut_test/data.csv").unwrap());
+            let mut writer = Cursor::new(vec![]);
+            let line = (&mut reader).lines().next().unwrap().ok().unwrap();
+            let mut cfg = mcut::Config::parse_field_as_name(line.clone(), b',', String::from("@lineno,id"));
+            cfg.sample = Some(sample);
+            cfg.tail = tail;
+            cfg.seed = seed;
+            cfg.write_header(&mut writer);
+            mcut::mcut(&mut reader, &mut writer, cfg);
+            let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+            let lines: Vec<&str> = actual.lines().collect();
+            assert_eq!("lineno,id", lines[0]);
+            assert_eq!(expected_len, lines.len() - 1, "{:?}, seed: {}", sample, seed);
+            // 元の順番を保持する
+            let line_no: Vec<usize> = lines[1..].iter().map(|l| l.split(',').next().unwrap().parse().unwrap()).collect();
+            let mut sorted = line_no.clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(sorted, line_no);
+        }
+    }
+}
+
+#[test]
+fn test_mcut_13() {
+    // 同じシードであれば同じ行が抽出される
+    let run = |sample: mcut::Sample, seed: u64| {
+        let mut reader = BufReader::new(File::open("tests/mcut_test/data.csv").unwrap());
+        let mut writer = Cursor::new(vec![]);
+        let line = (&mut reader).lines().next().unwrap().ok().unwrap();
+        let mut cfg = mcut::Config::parse_field_as_name(line.clone(), b',', String::from("id"));
+        cfg.sample = Some(sample);
+        cfg.seed = seed;
+        mcut::mcut(&mut reader, &mut writer, cfg);
+        String::from_utf8(writer.get_ref().to_vec()).unwrap()
+    };
+    for seed in 0..10 {
+        assert_eq!(run(mcut::Sample::Rows(2), seed), run(mcut::Sample::Rows(2), seed));
+        assert_eq!(run(mcut::Sample::Fraction(0.5), seed), run(mcut::Sample::Fraction(0.5), seed));
+    }
+}
+
+#[test]
+fn test_mcut_14() {
+    // 前置きの行とコメント行
+    let cases: Vec<(mcut::Preamble, bool, &str)> = vec![
+        (mcut::Preamble { skip_lines: 2, comment_prefix: Some(b"#".to_vec()), header_pattern: None },
+         false, "tests/mcut_test/expected_14.csv"),
+        (mcut::Preamble { skip_lines: 0, comment_prefix: Some(b"#".to_vec()), header_pattern: Some(Regex::new("^id,.*,narrow1$").unwrap()) },
+         true, "tests/mcut_test/expected_14_2.csv"),
+    ];
+    for (preamble, pass_comments, expected) in cases {
+        let mut reader = BufReader::new(File::open("tests/mcut_test/preamble.csv").unwrap());
+        let mut writer = Cursor::new(vec![]);
+        let header = mcut::read_header(&mut reader, &preamble).unwrap();
+        let mut cfg = mcut::Config::parse_field_as_name(header.line.clone(), b',', String::from("@lineno,@bytes_offset,id,title"));
+        header.apply(&mut cfg);
+        cfg.comment_prefix = preamble.comment_prefix.clone();
+        cfg.pass_comments = pass_comments;
+        cfg.limit = Some(2);
+        cfg.write_header(&mut writer);
+        mcut::mcut(&mut reader, &mut writer, cfg);
+        let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+        assert_eq!(read_all(expected), actual);
+    }
+}
+
+#[test]
+fn test_mcut_15() {
+    // --tail と --pass-comments を同時に指定した場合、コメント行は保持した行より先に出力される
+    let data = "id,title\n1,a\n# comment 1\n2,b\n3,c\n# comment 2\n";
+    let mut reader = BufReader::new(data.as_bytes());
+    let mut writer = Cursor::new(vec![]);
+    let line = (&mut reader).lines().next().unwrap().ok().unwrap();
+    let mut cfg = mcut::Config::parse_field_as_name(line, b',', String::from("id,title"));
+    cfg.comment_prefix = Some(b"#".to_vec());
+    cfg.pass_comments = true;
+    cfg.tail = Some(2);
+    mcut::mcut(&mut reader, &mut writer, cfg);
+    let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+    assert_eq!("# comment 1\n# comment 2\n2,b\n3,c\n", actual);
+}
+
+#[test]
+fn test_mcut_16() {
+    // 改行が CRLF の場合も @bytes_offset はヘッダ行の実際のバイト数から数える
+    let data = "# comment\r\nid,title\r\n1,a\r\n2,b\r\n";
+    let preamble = mcut::Preamble { skip_lines: 0, comment_prefix: Some(b"#".to_vec()), header_pattern: None };
+    let mut reader = BufReader::new(data.as_bytes());
+    let mut writer = Cursor::new(vec![]);
+    let header = mcut::read_header(&mut reader, &preamble).unwrap();
+    let mut cfg = mcut::Config::parse_field_as_name(header.line.clone(), b',', String::from("@bytes_offset,id"));
+    header.apply(&mut cfg);
+    cfg.write_first_line(&mut writer);
+    mcut::mcut(&mut reader, &mut writer, cfg);
+    let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+    assert_eq!("11,id\n21,1\n26,2\n", actual);
+}
+
+#[test]
+fn test_list_columns_1() {
+    let mut reader = BufReader::new(File::open("tests/mcut_test/ragged.csv").unwrap());
+    let mut writer = Cursor::new(vec![]);
+    let preamble = mcut::Preamble { skip_lines: 0, comment_prefix: None, header_pattern: None };
+    let header = mcut::read_header(&mut reader, &preamble).unwrap();
+    mcut::list_columns(&mut reader, &mut writer, &header, b',', None, 2);
+    let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+    assert_eq!(read_all("tests/mcut_test/expected_list_columns.tsv"), actual);
+}
+
+#[test]
+fn test_list_columns_2() {
+    // 行番号はヘッダより前の行とコメント行も数え、コメント行は値の出力とカラム数の確認から除く
+    let mut reader = BufReader::new(File::open("tests/mcut_test/ragged_preamble.csv").unwrap());
+    let mut writer = Cursor::new(vec![]);
+    let preamble = mcut::Preamble { skip_lines: 1, comment_prefix: Some(b"#".to_vec()), header_pattern: None };
+    let header = mcut::read_header(&mut reader, &preamble).unwrap();
+    mcut::list_columns(&mut reader, &mut writer, &header, b',', Some(b"#"), 2);
+    let actual: String = String::from_utf8(writer.get_ref().to_vec()).unwrap();
+    assert_eq!(read_all("tests/mcut_test/expected_list_columns_2.tsv"), actual);
+}
+
 fn read_all(file_name: &str) -> String {
     let mut f = File::open(file_name).ok().unwrap();
     let mut buf: String = String::new();
@@ -0,0 +1,87 @@
+extern crate mtools;
+
+use mtools::mjoin;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_mjoin_1() {
+    let mut left = BufReader::new(File::open("tests/mjoin_test/sales.csv").unwrap());
+    let mut right = BufReader::new(File::open("tests/mjoin_test/products.csv").unwrap());
+    let mut writer = vec![];
+    let (left_line, right_line) = (read_line(&mut left), read_line(&mut right));
+    let mut cfg = mjoin::Config::new(&left_line, &right_line, b',', "product_id", "product_id", false);
+    cfg.join_type = mjoin::JoinType::Left;
+    cfg.set_fields("L.date..product_id,R.title,L.price", false);
+    cfg.write_header(&mut writer).unwrap();
+    mjoin::mjoin(&mut left, &mut right, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mjoin_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_mjoin_2() {
+    // 左側でハッシュテーブルを構築する完全外部結合
+    let mut left = BufReader::new(File::open("tests/mjoin_test/products.csv").unwrap());
+    let mut right = BufReader::new(File::open("tests/mjoin_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let (left_line, right_line) = (read_line(&mut left), read_line(&mut right));
+    let mut cfg = mjoin::Config::new(&left_line, &right_line, b',', "0", "1", false);
+    cfg.join_type = mjoin::JoinType::Full;
+    cfg.build_side = mjoin::Side::Right;
+    cfg.write_header(&mut writer).unwrap();
+    mjoin::mjoin(&mut left, &mut right, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mjoin_test/expected_2.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_mjoin_3() {
+    // ソート済みのファイルのマージ結合
+    let mut left = BufReader::new(File::open("tests/mjoin_test/products.csv").unwrap());
+    let mut right = BufReader::new(File::open("tests/mjoin_test/sales_sorted.csv").unwrap());
+    let mut writer = vec![];
+    let (left_line, right_line) = (read_line(&mut left), read_line(&mut right));
+    let mut cfg = mjoin::Config::new(&left_line, &right_line, b',', "product_id", "product_id", false);
+    cfg.join_type = mjoin::JoinType::Full;
+    cfg.algorithm = mjoin::Algorithm::Merge;
+    cfg.write_header(&mut writer).unwrap();
+    mjoin::mjoin(&mut left, &mut right, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mjoin_test/expected_3.csv"), String::from_utf8(writer).unwrap());
+
+    // ソートされていないファイルはエラーにする
+    let mut left = BufReader::new(File::open("tests/mjoin_test/products.csv").unwrap());
+    let mut right = BufReader::new(File::open("tests/mjoin_test/sales.csv").unwrap());
+    let (left_line, right_line) = (read_line(&mut left), read_line(&mut right));
+    let mut cfg = mjoin::Config::new(&left_line, &right_line, b',', "product_id", "product_id", false);
+    cfg.algorithm = mjoin::Algorithm::Merge;
+    let err = mjoin::mjoin(&mut left, &mut right, &mut vec![], &cfg).unwrap_err();
+    assert!(err.to_string().starts_with("右側のファイルがキーでソートされていません: ヘッダを除いた4行目"));
+}
+
+#[test]
+fn test_mjoin_4() {
+    // ブロックリストに含まれる商品の行を除く
+    let mut left = BufReader::new(File::open("tests/mjoin_test/sales.csv").unwrap());
+    let mut right = BufReader::new(File::open("tests/mjoin_test/blocklist.csv").unwrap());
+    let mut writer = vec![];
+    let (left_line, right_line) = (read_line(&mut left), read_line(&mut right));
+    let mut cfg = mjoin::Config::new(&left_line, &right_line, b',', "product_id", "product_id", false);
+    cfg.join_type = mjoin::JoinType::Anti;
+    cfg.write_header(&mut writer).unwrap();
+    mjoin::mjoin(&mut left, &mut right, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mjoin_test/expected_4.csv"), String::from_utf8(writer).unwrap());
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line.trim_end().to_string()
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
@@ -0,0 +1,48 @@
+extern crate mtools;
+
+use mtools::mpaste;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_mpaste_1() {
+    let mut readers: Vec<BufReader<File>> = ["items", "scores", "ranks"].iter()
+        .map(|name| BufReader::new(File::open(format!("tests/mpaste_test/{}.csv", name)).unwrap()))
+        .collect();
+    let first_lines: Vec<String> = readers.iter_mut().map(|reader| read_line(reader)).collect();
+    let first_lines: Vec<&str> = first_lines.iter().map(|line| line.as_str()).collect();
+    let mut cfg = mpaste::Config::new(&first_lines, &["items", "scores", "ranks"], b',');
+    cfg.on_mismatch = mpaste::OnMismatch::Pad;
+    let mut writer = vec![];
+    cfg.write_header(&mut writer).unwrap();
+    mpaste::mpaste(&mut readers, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mpaste_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_mpaste_2() {
+    // 行数が異なる場合はエラーにする
+    let mut readers: Vec<BufReader<File>> = ["items", "ranks"].iter()
+        .map(|name| BufReader::new(File::open(format!("tests/mpaste_test/{}.csv", name)).unwrap()))
+        .collect();
+    let first_lines: Vec<String> = readers.iter_mut().map(|reader| read_line(reader)).collect();
+    let first_lines: Vec<&str> = first_lines.iter().map(|line| line.as_str()).collect();
+    let cfg = mpaste::Config::new(&first_lines, &["items", "ranks"], b',');
+    let err = mpaste::mpaste(&mut readers, &mut vec![], &cfg).unwrap_err();
+    assert_eq!("ranks の行数が他のファイルより少ないです: ヘッダを除いて2行です", err.to_string());
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line.trim_end().to_string()
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
@@ -0,0 +1,38 @@
+extern crate mtools;
+
+use mtools::msort;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_msort_1() {
+    let mut reader = BufReader::new(File::open("tests/msort_test/data.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let cfg = msort::Config::parse_keys(line.trim_end(), b',', "price:n:desc,id", false);
+    msort::msort(&mut reader, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/msort_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_msort_2() {
+    // 一時ファイルに書き出してマージする
+    let mut reader = BufReader::new(File::open("tests/msort_test/data.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut cfg = msort::Config::parse_keys(line.trim_end(), b',', "FILE:v", true);
+    cfg.buffer_size = 1;
+    msort::msort(&mut reader, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/msort_test/expected_2.csv"), String::from_utf8(writer).unwrap());
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
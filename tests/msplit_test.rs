@@ -0,0 +1,59 @@
+extern crate mtools;
+
+use mtools::msplit;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+
+#[test]
+fn test_msplit_1() {
+    // narrow1 の値ごとに分割する。開いておくファイル数を超えた場合は閉じたファイルに追記する
+    let dir = output_dir("1");
+    let mut reader = BufReader::new(File::open("tests/msplit_test/sales.csv").unwrap());
+    let first_line = read_line(&mut reader);
+    let mode = msplit::Mode::keys(&first_line, b',', "narrow1", false);
+    let mut cfg = msplit::Config::new(b',', mode, &format!("{}/{{}}.csv", dir.display()));
+    cfg.max_open_files = 1;
+    let paths = msplit::msplit(&mut reader, &first_line, &cfg).unwrap();
+    assert_eq!(vec![dir.join("果物.csv"), dir.join("野菜.csv"), dir.join("肉.csv")], paths);
+    for (i, path) in paths.iter().enumerate() {
+        assert_eq!(read_all(&format!("tests/msplit_test/expected_key_{}.csv", i + 1)), read_all(&path.to_string_lossy()));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_msplit_2() {
+    // 2行ごとに分割する
+    let dir = output_dir("2");
+    let mut reader = BufReader::new(File::open("tests/msplit_test/sales.csv").unwrap());
+    let first_line = read_line(&mut reader);
+    let cfg = msplit::Config::new(b',', msplit::Mode::Rows(2), &format!("{}/part-{{}}.csv", dir.display()));
+    let paths = msplit::msplit(&mut reader, &first_line, &cfg).unwrap();
+    assert_eq!(vec![dir.join("part-0001.csv"), dir.join("part-0002.csv"), dir.join("part-0003.csv")], paths);
+    for (i, path) in paths.iter().enumerate() {
+        assert_eq!(read_all(&format!("tests/msplit_test/expected_rows_{}.csv", i + 1)), read_all(&path.to_string_lossy()));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn output_dir(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("mtools-msplit-test-{}-{}", std::process::id(), name))
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> String {
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    line.trim_end().to_string()
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
@@ -0,0 +1,40 @@
+extern crate mtools;
+
+use mtools::mstats;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_mstats_1() {
+    let mut reader = BufReader::new(File::open("tests/mstats_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let cfg = mstats::Config::parse_fields(line.trim_end(), b',', None, false);
+    mstats::mstats(&mut reader, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mstats_test/expected.csv"), String::from_utf8(writer).unwrap());
+}
+
+#[test]
+fn test_mstats_2() {
+    // HyperLogLog で推定した重複を除いた値の数をJSONで出力する
+    let mut reader = BufReader::new(File::open("tests/mstats_test/sales.csv").unwrap());
+    let mut writer = vec![];
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut cfg = mstats::Config::parse_fields(line.trim_end(), b',', Some("DATE,Price"), true);
+    cfg.approx_distinct = true;
+    cfg.top = 1;
+    cfg.format = mstats::Format::Json;
+    mstats::mstats(&mut reader, &mut writer, &cfg).unwrap();
+    assert_eq!(read_all("tests/mstats_test/expected.json"), String::from_utf8(writer).unwrap());
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
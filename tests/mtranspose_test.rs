@@ -0,0 +1,28 @@
+extern crate mtools;
+
+use mtools::mtranspose;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::fs::File;
+
+
+#[test]
+fn test_mtranspose_1() {
+    for low_memory in &[false, true] {
+        let mut reader = BufReader::new(File::open("tests/mtranspose_test/settings.csv").unwrap());
+        let mut first_line = String::new();
+        reader.read_line(&mut first_line).unwrap();
+        let mut cfg = mtranspose::Config::new(b',');
+        cfg.low_memory = *low_memory;
+        let mut writer = vec![];
+        mtranspose::mtranspose(&mut reader, &mut writer, first_line.trim_end(), &cfg).unwrap();
+        assert_eq!(read_all("tests/mtranspose_test/expected.csv"), String::from_utf8(writer).unwrap());
+    }
+}
+
+fn read_all(file_name: &str) -> String {
+    let mut f = File::open(file_name).ok().unwrap();
+    let mut buf: String = String::new();
+    f.read_to_string(&mut buf).ok().unwrap();
+    buf
+}
@@ -0,0 +1,38 @@
+extern crate mtools;
+
+use mtools::profile;
+use std::path::PathBuf;
+
+
+#[test]
+fn test_load_1() {
+    // 先に指定したファイルのプロファイルを優先する
+    let paths = vec![PathBuf::from("tests/profile_test/local.toml"), PathBuf::from("tests/profile_test/config.toml")];
+    let options = profile::load("partner_a", &paths).unwrap();
+    assert_eq!(Some(&"id".to_string()), options.get("-F"));
+    assert_eq!(None, options.get("--unique-header"));
+
+    let options = profile::load("partner_b", &paths).unwrap();
+    assert_eq!(Some(&"\t".to_string()), options.get("-d"));
+    assert_eq!(Some(&"1,0".to_string()), options.get("-f"));
+}
+
+#[test]
+fn test_load_2() {
+    // 存在しないファイルは無視する
+    let paths = vec![PathBuf::from("tests/profile_test/not_exists.toml"), PathBuf::from("tests/profile_test/config.toml")];
+    let options = profile::load("partner_a", &paths).unwrap();
+    assert_eq!(Some(&"--unique-header".to_string()), options.get("--unique-header"));
+    let err = profile::load("partner_c", &paths).unwrap_err();
+    assert!(err.starts_with("プロファイルが見つかりません: partner_c\n"));
+}
+
+#[test]
+fn test_load_3() {
+    // 読み込めないファイルは無視せずにエラーにする
+    let paths = vec![PathBuf::from("tests/profile_test/invalid_utf8.toml"), PathBuf::from("tests/profile_test/config.toml")];
+    let err = profile::load("partner_a", &paths).unwrap_err();
+    assert!(err.starts_with("設定ファイルの読み込みに失敗しました: tests/profile_test/invalid_utf8.toml\n"));
+    let paths = vec![PathBuf::from("tests/profile_test")];
+    assert!(profile::load("partner_a", &paths).unwrap_err().starts_with("設定ファイルの読み込みに失敗しました: "));
+}